@@ -1,4 +1,8 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    fmt,
+    ops::{Add, Mul},
+};
 
 fn simulate_fish_part1(mut fish: Vec<u8>, days: usize) -> usize {
     let mut next_fish = Vec::with_capacity(fish.len());
@@ -43,13 +47,317 @@ fn simulate_fish_fast_part2(fish: &[u8], days: usize) -> usize {
     result
 }
 
+////////// Population model
+
+trait Count: Clone + Add<Output = Self> + Mul<Output = Self> {
+    fn zero() -> Self;
+    fn one() -> Self;
+}
+
+impl Count for u64 {
+    fn zero() -> Self {
+        0
+    }
+    fn one() -> Self {
+        1
+    }
+}
+
+impl Count for f64 {
+    fn zero() -> Self {
+        0.0
+    }
+    fn one() -> Self {
+        1.0
+    }
+}
+
+impl Count for u128 {
+    fn zero() -> Self {
+        0
+    }
+    fn one() -> Self {
+        1
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Modular<const M: u64>(u64);
+
+impl<const M: u64> Add for Modular<M> {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Modular(((self.0 as u128 + rhs.0 as u128) % M as u128) as u64)
+    }
+}
+
+impl<const M: u64> Mul for Modular<M> {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Modular((self.0 as u128 * rhs.0 as u128 % M as u128) as u64)
+    }
+}
+
+impl<const M: u64> Count for Modular<M> {
+    fn zero() -> Self {
+        Modular(0)
+    }
+    fn one() -> Self {
+        Modular(1 % M)
+    }
+}
+
+// Unsigned big integer, little-endian base 2^32 limbs without trailing zeros.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct BigCount(Vec<u32>);
+
+impl BigCount {
+    fn trimmed(mut limbs: Vec<u32>) -> Self {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        BigCount(limbs)
+    }
+}
+
+impl From<u128> for BigCount {
+    fn from(mut n: u128) -> Self {
+        let mut limbs = vec![];
+        while n > 0 {
+            limbs.push(n as u32);
+            n >>= 32;
+        }
+        BigCount(limbs)
+    }
+}
+
+impl Add for BigCount {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        let mut limbs = Vec::with_capacity(self.0.len().max(rhs.0.len()) + 1);
+        let mut carry = 0;
+        for i in 0..self.0.len().max(rhs.0.len()) {
+            let a = *self.0.get(i).unwrap_or(&0) as u64;
+            let b = *rhs.0.get(i).unwrap_or(&0) as u64;
+            let sum = a + b + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+        limbs.push(carry as u32);
+        BigCount::trimmed(limbs)
+    }
+}
+
+impl Mul for BigCount {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        let mut limbs = vec![0u32; self.0.len() + rhs.0.len()];
+        for (i, &a) in self.0.iter().enumerate() {
+            let mut carry = 0;
+            for (j, &b) in rhs.0.iter().enumerate() {
+                let product = a as u64 * b as u64 + limbs[i + j] as u64 + carry;
+                limbs[i + j] = product as u32;
+                carry = product >> 32;
+            }
+            limbs[i + rhs.0.len()] = carry as u32;
+        }
+        BigCount::trimmed(limbs)
+    }
+}
+
+impl Count for BigCount {
+    fn zero() -> Self {
+        BigCount(vec![])
+    }
+    fn one() -> Self {
+        BigCount(vec![1])
+    }
+}
+
+impl fmt::Display for BigCount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const CHUNK: u64 = 1_000_000_000;
+        let mut limbs = self.0.clone();
+        let mut chunks = vec![];
+        while !limbs.is_empty() {
+            let mut rem = 0;
+            for limb in limbs.iter_mut().rev() {
+                let cur = (rem << 32) | *limb as u64;
+                *limb = (cur / CHUNK) as u32;
+                rem = cur % CHUNK;
+            }
+            chunks.push(rem);
+            limbs = BigCount::trimmed(limbs).0;
+        }
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((first, rest)) => {
+                write!(f, "{first}")?;
+                rest.iter().rev().try_for_each(|c| write!(f, "{c:09}"))
+            }
+        }
+    }
+}
+
+type Matrix<T> = Vec<Vec<T>>;
+
+fn mat_mul<T: Count>(a: &Matrix<T>, b: &Matrix<T>) -> Matrix<T> {
+    let n = a.len();
+    (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| (0..n).fold(T::zero(), |sum, k| sum + a[i][k].clone() * b[k][j].clone()))
+                .collect()
+        })
+        .collect()
+}
+
+fn mat_pow<T: Count>(mut base: Matrix<T>, mut exp: u64) -> Matrix<T> {
+    let n = base.len();
+    let mut result: Matrix<T> = (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| if i == j { T::one() } else { T::zero() })
+                .collect()
+        })
+        .collect();
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mat_mul(&result, &base);
+        }
+        base = mat_mul(&base, &base);
+        exp >>= 1;
+    }
+    result
+}
+
+// Fish with timer 0 spawn a newborn and go back to the reset timer.
+struct PopulationModel {
+    reset_timer: usize,
+    newborn_timer: usize,
+}
+
+impl PopulationModel {
+    fn new(reset_timer: usize, newborn_timer: usize) -> Self {
+        Self {
+            reset_timer,
+            newborn_timer,
+        }
+    }
+
+    // Enough buckets for both timers and for the oldest fish given; buckets
+    // above the timers only ever count down.
+    fn bucket_count(&self, fish: &[u8]) -> usize {
+        let timers = self.reset_timer.max(self.newborn_timer);
+        fish.iter().map(|&f| f as usize).fold(timers, usize::max) + 1
+    }
+
+    fn buckets<T: Count>(&self, fish: &[u8]) -> Vec<T> {
+        let mut buckets = vec![T::zero(); self.bucket_count(fish)];
+        for &f in fish {
+            buckets[f as usize] = buckets[f as usize].clone() + T::one();
+        }
+        buckets
+    }
+
+    fn step<T: Count>(&self, buckets: &[T]) -> Vec<T> {
+        let mut next = buckets[1..].to_vec();
+        next.push(T::zero());
+        let spawning = buckets[0].clone();
+        next[self.reset_timer] = next[self.reset_timer].clone() + spawning.clone();
+        next[self.newborn_timer] = next[self.newborn_timer].clone() + spawning;
+        next
+    }
+
+    fn simulate<T: Count>(&self, fish: &[u8], days: usize) -> Vec<T> {
+        (0..days).fold(self.buckets(fish), |buckets, _| self.step(&buckets))
+    }
+
+    // Column j holds where the fish of bucket j end up after one day.
+    fn transition<T: Count>(&self, n: usize) -> Matrix<T> {
+        let mut matrix = vec![vec![T::zero(); n]; n];
+        for (i, row) in matrix.iter_mut().enumerate().take(n - 1) {
+            row[i + 1] = T::one();
+        }
+        matrix[self.reset_timer][0] = matrix[self.reset_timer][0].clone() + T::one();
+        matrix[self.newborn_timer][0] = matrix[self.newborn_timer][0].clone() + T::one();
+        matrix
+    }
+
+    fn buckets_after<T: Count>(&self, fish: &[u8], days: u64) -> Vec<T> {
+        let matrix = mat_pow(self.transition::<T>(self.bucket_count(fish)), days);
+        let buckets = self.buckets::<T>(fish);
+        matrix
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(&buckets)
+                    .fold(T::zero(), |sum, (m, b)| sum + m.clone() * b.clone())
+            })
+            .collect()
+    }
+
+    fn population_after<T: Count>(&self, fish: &[u8], days: u64) -> T {
+        self.buckets_after::<T>(fish, days)
+            .into_iter()
+            .fold(T::zero(), |sum, b| sum + b)
+    }
+
+    fn series<'a, T: Count + 'a>(&'a self, fish: &[u8]) -> impl Iterator<Item = Vec<T>> + 'a {
+        std::iter::successors(Some(self.buckets(fish)), move |buckets| {
+            Some(self.step(buckets))
+        })
+    }
+
+    fn series_csv<T: Count + fmt::Display>(&self, fish: &[u8], days: usize) -> String {
+        let timers = (0..self.bucket_count(fish)).map(|t| format!(",timer{t}"));
+        let mut csv = format!("day,population{}\n", timers.collect::<String>());
+        for (day, buckets) in self.series::<T>(fish).take(days + 1).enumerate() {
+            let population = buckets.iter().cloned().fold(T::zero(), |sum, b| sum + b);
+            let counts = buckets.iter().map(|b| format!(",{b}")).collect::<String>();
+            csv += &format!("{day},{population}{counts}\n");
+        }
+        csv
+    }
+
+    // Dominant eigenvalue of the transition matrix by power iteration. The
+    // identity is added first so the iteration can't oscillate when the timer
+    // cycles share a period.
+    fn growth_rate(&self) -> f64 {
+        let n = self.bucket_count(&[]);
+        let mut matrix = self.transition::<f64>(n);
+        for (i, row) in matrix.iter_mut().enumerate() {
+            row[i] += 1.0;
+        }
+        let mut vector = vec![1.0; n];
+        let mut rate = 0.0;
+        for _ in 0..10_000 {
+            let next: Vec<f64> = matrix
+                .iter()
+                .map(|row| row.iter().zip(&vector).map(|(m, v)| m * v).sum())
+                .collect();
+            let norm: f64 = next.iter().sum();
+            vector = next.iter().map(|v| v / norm).collect();
+            if (norm - rate).abs() < 1e-12 {
+                break;
+            }
+            rate = norm;
+        }
+        rate - 1.0
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
 
     use crate::day06::simulate_fish_fast_part2;
 
+    use super::mat_pow;
     use super::simulate_fish_part1;
+    use super::BigCount;
+    use super::Modular;
+    use super::PopulationModel;
     const EXAMPLE_06: &str = "3,4,3,1,2";
     #[test]
     fn example06_part1() {
@@ -84,4 +392,98 @@ mod tests {
         let ans = simulate_fish_fast_part2(&fish, 256);
         assert_eq!(ans, 1622533344325);
     }
+    #[test]
+    fn day06_model() {
+        let input = fs::read_to_string("input/day06").unwrap();
+        let fish: Vec<u8> = input
+            .split(',')
+            .map(|n| n.trim().parse().unwrap())
+            .collect();
+        let model = PopulationModel::new(6, 8);
+        assert_eq!(model.population_after::<u64>(&fish, 80), 358214);
+        assert_eq!(model.population_after::<u64>(&fish, 256), 1622533344325);
+        let simulated: u64 = model.simulate::<u64>(&fish, 256).into_iter().sum();
+        assert_eq!(simulated, 1622533344325);
+    }
+    #[test]
+    fn model_arithmetic() {
+        let fish: Vec<u8> = EXAMPLE_06.split(',').map(|n| n.parse().unwrap()).collect();
+        let model = PopulationModel::new(6, 8);
+        let exact = model.population_after::<u128>(&fish, 700);
+        let big = model.population_after::<BigCount>(&fish, 700);
+        assert_eq!(big, BigCount::from(exact));
+        assert_eq!(big.to_string(), exact.to_string());
+        const M: u64 = 1_000_000_007;
+        let modular = model.population_after::<Modular<M>>(&fish, 700);
+        assert_eq!(modular, Modular((exact % M as u128) as u64));
+        let simulated = model
+            .simulate::<Modular<M>>(&fish, 10_000)
+            .into_iter()
+            .fold(Modular(0), |sum, b| sum + b);
+        assert_eq!(
+            model.population_after::<Modular<M>>(&fish, 10_000),
+            simulated
+        );
+        // 10^18 days by one matrix power, and again as two halves of 5 * 10^17.
+        let half = 500_000_000_000_000_000;
+        let matrix = mat_pow(
+            model.transition::<Modular<M>>(model.bucket_count(&fish)),
+            half,
+        );
+        let buckets = model.buckets_after::<Modular<M>>(&fish, half);
+        let twice = matrix
+            .iter()
+            .flat_map(|row| row.iter().zip(&buckets).map(|(&m, &b)| m * b))
+            .fold(Modular(0), |sum, count| sum + count);
+        assert_eq!(model.population_after::<Modular<M>>(&fish, 2 * half), twice);
+    }
+    #[test]
+    fn model_timers() {
+        let fish: Vec<u8> = EXAMPLE_06.split(',').map(|n| n.parse().unwrap()).collect();
+        let model = PopulationModel::new(2, 4);
+        for days in [0, 1, 5, 17, 40] {
+            let simulated: u64 = model.simulate::<u64>(&fish, days).into_iter().sum();
+            assert_eq!(model.population_after::<u64>(&fish, days as u64), simulated);
+        }
+        // Fish older than both timers.
+        let old = [5, 9];
+        for days in [0, 4, 5, 6, 30] {
+            let simulated: u64 = model.simulate::<u64>(&old, days).into_iter().sum();
+            assert_eq!(model.population_after::<u64>(&old, days as u64), simulated);
+        }
+        assert_eq!(model.population_after::<u64>(&old, 6), 3);
+    }
+    #[test]
+    fn model_series() {
+        let fish: Vec<u8> = EXAMPLE_06.split(',').map(|n| n.parse().unwrap()).collect();
+        let model = PopulationModel::new(6, 8);
+        let mut series = model.series::<u64>(&fish);
+        assert_eq!(series.next().unwrap(), vec![0, 1, 1, 2, 1, 0, 0, 0, 0]);
+        let populations: Vec<u64> = series.map(|b| b.into_iter().sum()).take(80).collect();
+        assert_eq!(populations[17], 26);
+        assert_eq!(populations[79], 5934);
+        let csv = model.series_csv::<u64>(&fish, 18);
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "day,population,timer0,timer1,timer2,timer3,timer4,timer5,timer6,timer7,timer8"
+        );
+        assert_eq!(lines.last().unwrap(), "18,26,3,5,3,2,2,1,5,1,4");
+    }
+    #[test]
+    fn model_growth_rate() {
+        let fish = [0, 1, 3];
+        // Two days apart, since the (1, 3) model alternates between odd and even days.
+        for model in [PopulationModel::new(6, 8), PopulationModel::new(1, 3)] {
+            let populations: Vec<f64> = model
+                .series::<f64>(&fish)
+                .map(|b| b.into_iter().sum())
+                .skip(2000)
+                .step_by(2)
+                .take(2)
+                .collect();
+            let ratio = (populations[1] / populations[0]).sqrt();
+            assert!((model.growth_rate() - ratio).abs() < 1e-9);
+        }
+    }
 }
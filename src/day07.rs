@@ -1,32 +1,218 @@
 fn find_min_cost(crabs: &[i32]) -> i32 {
-    let mut pos = *crabs.iter().min().unwrap();
-    let mut min_cost = crabs.iter().map(|c| (c - pos).abs()).sum();
-    loop {
-        pos += 1;
-        let new_cost: i32 = crabs.iter().map(|c| (c - pos).abs()).sum();
-        if new_cost < min_cost {
-            min_cost = new_cost;
-        } else {
-            break min_cost;
+    align(crabs, &Linear).cost as i32
+}
+
+fn find_min_cost_part2(crabs: &[i32]) -> i32 {
+    align(crabs, &Triangular).cost as i32
+}
+
+// Crabs are (position, weight) pairs; a crab's fuel is its weight times the
+// model's cost for the distance it moves.
+trait CostModel {
+    fn cost(&self, distance: i64) -> i64;
+    // Positions known to contain an optimum, for models with a closed form.
+    fn candidates(&self, _crabs: &[(i32, i64)]) -> Option<Vec<i32>> {
+        None
+    }
+    // Whether cost(a + b) == cost(a) + cost(b), so that points can be aligned
+    // one axis at a time.
+    fn separable(&self) -> bool {
+        false
+    }
+}
+
+struct Linear;
+
+impl CostModel for Linear {
+    fn cost(&self, distance: i64) -> i64 {
+        distance
+    }
+    fn candidates(&self, crabs: &[(i32, i64)]) -> Option<Vec<i32>> {
+        Some(vec![weighted_median(crabs)])
+    }
+    fn separable(&self) -> bool {
+        true
+    }
+}
+
+struct Triangular;
+
+impl CostModel for Triangular {
+    fn cost(&self, distance: i64) -> i64 {
+        distance * (distance + 1) / 2
+    }
+    // The real optimum lies within 1/2 of the mean.
+    fn candidates(&self, crabs: &[(i32, i64)]) -> Option<Vec<i32>> {
+        let (floor, ceil) = mean_bounds(crabs);
+        Some((floor - 1..=ceil + 1).collect())
+    }
+}
+
+struct Quadratic;
+
+impl CostModel for Quadratic {
+    fn cost(&self, distance: i64) -> i64 {
+        distance * distance
+    }
+    fn candidates(&self, crabs: &[(i32, i64)]) -> Option<Vec<i32>> {
+        let (floor, ceil) = mean_bounds(crabs);
+        Some(vec![floor, ceil])
+    }
+}
+
+// Any cost that is convex in the distance, minimised by ternary search.
+struct Convex<F: Fn(i64) -> i64>(F);
+
+impl<F: Fn(i64) -> i64> CostModel for Convex<F> {
+    fn cost(&self, distance: i64) -> i64 {
+        (self.0)(distance)
+    }
+}
+
+fn weighted_median(crabs: &[(i32, i64)]) -> i32 {
+    let total: i64 = crabs.iter().map(|(_, w)| w).sum();
+    let mut sorted = crabs.to_vec();
+    sorted.sort_unstable();
+    let mut seen = 0;
+    for (pos, weight) in sorted {
+        seen += weight;
+        if 2 * seen >= total {
+            return pos;
         }
     }
+    unreachable!()
 }
 
-fn find_min_cost_part2(crabs: &[i32]) -> i32 {
-    let min_pos = *crabs.iter().min().unwrap();
-    let max_pos = *crabs.iter().max().unwrap();
-    let mut min_cost = i32::MAX;
-    for pos in min_pos..=max_pos {
-        let new_cost: i32 = crabs
+fn mean_bounds(crabs: &[(i32, i64)]) -> (i32, i32) {
+    let sum: i64 = crabs.iter().map(|&(c, w)| c as i64 * w).sum();
+    let total: i64 = crabs.iter().map(|(_, w)| w).sum();
+    (
+        sum.div_euclid(total) as i32,
+        (sum + total - 1).div_euclid(total) as i32,
+    )
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Alignment<P> {
+    position: P,
+    cost: i64,
+}
+
+fn total_cost(crabs: &[(i32, i64)], model: &impl CostModel, pos: i32) -> i64 {
+    crabs
+        .iter()
+        .map(|&(c, w)| w * model.cost((c - pos).abs() as i64))
+        .sum()
+}
+
+fn align(crabs: &[i32], model: &impl CostModel) -> Alignment<i32> {
+    let weighted = crabs.iter().map(|&c| (c, 1)).collect::<Vec<_>>();
+    align_weighted(&weighted, model)
+}
+
+fn align_weighted(crabs: &[(i32, i64)], model: &impl CostModel) -> Alignment<i32> {
+    let cost = |pos| total_cost(crabs, model, pos);
+    let position = match model.candidates(crabs) {
+        Some(candidates) => candidates.into_iter().min_by_key(|&p| cost(p)).unwrap(),
+        None => {
+            let min = crabs.iter().map(|&(c, _)| c).min().unwrap();
+            let max = crabs.iter().map(|&(c, _)| c).max().unwrap();
+            ternary_search(min, max, cost)
+        }
+    };
+    Alignment {
+        position,
+        cost: cost(position),
+    }
+}
+
+// A crab's fuel is the model's cost of its Manhattan distance to the meeting
+// point. Separable models are aligned one axis at a time; otherwise a ternary
+// search along each axis, nested inside the previous one, gets close and a
+// walk to better neighbouring points finishes off.
+fn align_points<const N: usize>(
+    crabs: &[([i32; N], i64)],
+    model: &impl CostModel,
+) -> Alignment<[i32; N]> {
+    if model.separable() {
+        let mut alignment = Alignment {
+            position: [0; N],
+            cost: 0,
+        };
+        for axis in 0..N {
+            let projected = crabs.iter().map(|(p, w)| (p[axis], *w)).collect::<Vec<_>>();
+            let aligned = align_weighted(&projected, model);
+            alignment.position[axis] = aligned.position;
+            alignment.cost += aligned.cost;
+        }
+        return alignment;
+    }
+    let cost = |pos: &[i32; N]| {
+        crabs
             .iter()
-            .map(|c| (c - pos).abs())
-            .map(|steps| (1..=steps).sum::<i32>())
-            .sum();
-        if new_cost < min_cost {
-            min_cost = new_cost;
+            .map(|(c, w)| {
+                let distance: i64 = c.iter().zip(pos).map(|(c, p)| (c - p).abs() as i64).sum();
+                w * model.cost(distance)
+            })
+            .sum::<i64>()
+    };
+    let mut position = nested_search(crabs, &cost, [0; N], 0);
+    let mut best = cost(&position);
+    loop {
+        let better = (0..3usize.pow(N as u32))
+            .map(|offsets| {
+                let mut neighbor = position;
+                for (axis, p) in neighbor.iter_mut().enumerate() {
+                    *p += (offsets / 3usize.pow(axis as u32) % 3) as i32 - 1;
+                }
+                (cost(&neighbor), neighbor)
+            })
+            .min_by_key(|&(cost, _)| cost)
+            .filter(|&(cost, _)| cost < best);
+        match better {
+            Some((cost, neighbor)) => (best, position) = (cost, neighbor),
+            None => break,
         }
     }
-    min_cost
+    Alignment {
+        position,
+        cost: best,
+    }
+}
+
+// The best point found by ternary search along `axis`, with the axes after it
+// searched the same way for every probe.
+fn nested_search<const N: usize>(
+    crabs: &[([i32; N], i64)],
+    cost: &impl Fn(&[i32; N]) -> i64,
+    position: [i32; N],
+    axis: usize,
+) -> [i32; N] {
+    if axis == N {
+        return position;
+    }
+    let min = crabs.iter().map(|(c, _)| c[axis]).min().unwrap();
+    let max = crabs.iter().map(|(c, _)| c[axis]).max().unwrap();
+    let best_with = move |p: i32| {
+        let mut position = position;
+        position[axis] = p;
+        nested_search(crabs, cost, position, axis + 1)
+    };
+    best_with(ternary_search(min, max, |p| cost(&best_with(p))))
+}
+
+// Leftmost minimum of a convex function on lo..=hi.
+fn ternary_search(mut lo: i32, mut hi: i32, f: impl Fn(i32) -> i64) -> i32 {
+    while hi - lo > 2 {
+        let m1 = lo + (hi - lo) / 3;
+        let m2 = hi - (hi - lo) / 3;
+        match f(m1).cmp(&f(m2)) {
+            std::cmp::Ordering::Less => hi = m2 - 1,
+            std::cmp::Ordering::Greater => lo = m1 + 1,
+            std::cmp::Ordering::Equal => (lo, hi) = (m1, m2),
+        }
+    }
+    (lo..=hi).min_by_key(|&p| f(p)).unwrap()
 }
 
 #[cfg(test)]
@@ -35,7 +221,15 @@ mod tests {
 
     use crate::day07::find_min_cost_part2;
 
+    use super::align;
+    use super::align_points;
+    use super::align_weighted;
     use super::find_min_cost;
+    use super::Alignment;
+    use super::Convex;
+    use super::Linear;
+    use super::Quadratic;
+    use super::Triangular;
     const EXAMPLE_07: &str = "16,1,2,0,4,2,7,1,2,14";
     #[test]
     fn example07_part1() {
@@ -75,4 +269,110 @@ mod tests {
         let ans = find_min_cost_part2(&crabs);
         assert_eq!(ans, 101571302);
     }
+    #[test]
+    fn cost_models() {
+        let input = fs::read_to_string("input/day07").unwrap();
+        let crabs: Vec<i32> = input
+            .split(',')
+            .map(|n| n.trim().parse().unwrap())
+            .collect();
+        let linear = align(&crabs, &Linear);
+        assert_eq!(linear.cost, 352997);
+        assert_eq!(align(&crabs, &Convex(|d| d)).cost, linear.cost);
+        let triangular = align(&crabs, &Triangular);
+        assert_eq!(triangular.cost, 101571302);
+        assert_eq!(align(&crabs, &Convex(|d| d * (d + 1) / 2)), triangular);
+        let quadratic = align(&crabs, &Quadratic);
+        assert_eq!(align(&crabs, &Convex(|d| d * d)), quadratic);
+    }
+    #[test]
+    fn example07_quadratic() {
+        let crabs: Vec<i32> = EXAMPLE_07
+            .split(',')
+            .map(|n| n.trim().parse().unwrap())
+            .collect();
+        let brute_force = (0..=16)
+            .map(|pos| crabs.iter().map(|c| ((c - pos) as i64).pow(2)).sum::<i64>())
+            .min()
+            .unwrap();
+        let quadratic = align(&crabs, &Quadratic);
+        assert_eq!(
+            quadratic,
+            Alignment {
+                position: 5,
+                cost: brute_force
+            }
+        );
+    }
+    #[test]
+    fn example07_weighted() {
+        let crabs: Vec<(i32, i64)> = EXAMPLE_07
+            .split(',')
+            .map(|n| (n.trim().parse().unwrap(), 1))
+            .collect();
+        assert_eq!(align_weighted(&crabs, &Linear).cost, 37);
+        let mut heavy = crabs.clone();
+        heavy[0].1 = 100;
+        assert_eq!(align_weighted(&heavy, &Linear).position, 16);
+        let triangular = align_weighted(&heavy, &Triangular);
+        let convex = align_weighted(&heavy, &Convex(|d| d * (d + 1) / 2));
+        assert_eq!(triangular, convex);
+    }
+    #[test]
+    fn example07_points() {
+        let crabs: [([i32; 2], i64); 4] = [([0, 0], 1), ([4, 1], 2), ([1, 6], 1), ([9, 3], 1)];
+        let brute_force = |cost: fn(i64) -> i64| {
+            (0..=9)
+                .flat_map(|x| (0..=6).map(move |y| (x, y)))
+                .map(|(x, y)| {
+                    crabs
+                        .iter()
+                        .map(|([cx, cy], w)| w * cost(((cx - x).abs() + (cy - y).abs()) as i64))
+                        .sum::<i64>()
+                })
+                .min()
+                .unwrap()
+        };
+        let linear = align_points(&crabs, &Linear);
+        assert_eq!(linear.position, [4, 1]);
+        assert_eq!(linear.cost, brute_force(|d| d));
+        assert_eq!(
+            align_points(&crabs, &Triangular).cost,
+            brute_force(|d| d * (d + 1) / 2)
+        );
+        assert_eq!(
+            align_points(&crabs, &Convex(|d| d * d * d)).cost,
+            brute_force(|d| d * d * d)
+        );
+    }
+    #[test]
+    fn points_in_3d() {
+        let crabs: Vec<([i32; 3], i64)> = (0..12)
+            .map(|i| ([i * 7 % 11, i * i % 5, (i * 3) % 8], 1 + i as i64 % 3))
+            .collect();
+        let brute_force = |cost: fn(i64) -> i64| {
+            (0..11)
+                .flat_map(|x| (0..5).flat_map(move |y| (0..8).map(move |z| [x, y, z])))
+                .map(|p: [i32; 3]| {
+                    crabs
+                        .iter()
+                        .map(|(c, w)| {
+                            let d: i32 = c.iter().zip(p).map(|(c, p)| (c - p).abs()).sum();
+                            w * cost(d as i64)
+                        })
+                        .sum::<i64>()
+                })
+                .min()
+                .unwrap()
+        };
+        assert_eq!(align_points(&crabs, &Linear).cost, brute_force(|d| d));
+        assert_eq!(
+            align_points(&crabs, &Triangular).cost,
+            brute_force(|d| d * (d + 1) / 2)
+        );
+        assert_eq!(
+            align_points(&crabs, &Quadratic).cost,
+            brute_force(|d| d * d)
+        );
+    }
 }
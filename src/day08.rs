@@ -1,4 +1,6 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
 
 fn part_1(s: &str) -> usize {
     let (_, output) = s.split_once('|').unwrap();
@@ -9,65 +11,412 @@ fn part_1(s: &str) -> usize {
 }
 
 fn part_2(s: &str) -> usize {
+    decode_entry(s).unwrap()
+}
+
+const SEGMENTS: &str = "abcdefg";
+const DIGITS: [(char, &str); 10] = [
+    ('0', "abcefg"),
+    ('1', "cf"),
+    ('2', "acdeg"),
+    ('3', "acdfg"),
+    ('4', "bcdf"),
+    ('5', "abdfg"),
+    ('6', "abdefg"),
+    ('7', "acf"),
+    ('8', "abcdefg"),
+    ('9', "abcdfg"),
+];
+
+#[derive(Debug, PartialEq, Eq)]
+enum DecodeError {
+    UnknownWire(char),
+    // Masks have room for at most 32 segments.
+    TooManySegments(usize),
+    // No wiring turns every pattern into a glyph.
+    Inconsistent,
+    // More than one wiring fits the patterns.
+    Ambiguous,
+}
+
+// The segments of a display and which of them light up for each glyph.
+// Wires carry the same names as the segments they should drive.
+struct DisplayDef {
+    segments: Vec<char>,
+    glyphs: Vec<(char, u32)>,
+}
+
+impl DisplayDef {
+    fn new(segments: &str, glyphs: &[(char, &str)]) -> Result<Self, DecodeError> {
+        let mut display = DisplayDef {
+            segments: segments.chars().collect(),
+            glyphs: vec![],
+        };
+        if display.segments.len() > 32 {
+            return Err(DecodeError::TooManySegments(display.segments.len()));
+        }
+        for &(glyph, lit) in glyphs {
+            let mask = display.to_mask(lit)?;
+            display.glyphs.push((glyph, mask));
+        }
+        Ok(display)
+    }
+
+    fn to_mask(&self, pattern: &str) -> Result<u32, DecodeError> {
+        pattern.chars().try_fold(0, |mask, c| {
+            let i = self
+                .segments
+                .iter()
+                .position(|&s| s == c)
+                .ok_or(DecodeError::UnknownWire(c))?;
+            Ok(mask | 1 << i)
+        })
+    }
+
+    fn masks(&self) -> impl Iterator<Item = u32> + Clone + '_ {
+        self.glyphs.iter().map(|&(_, mask)| mask)
+    }
+}
+
+impl Default for DisplayDef {
+    fn default() -> Self {
+        DisplayDef::new(SEGMENTS, &DIGITS).unwrap()
+    }
+}
+
+fn decode_entry(s: &str) -> Result<usize, DecodeError> {
+    Ok(decode_glyphs(&DisplayDef::default(), s)?.parse().unwrap())
+}
+
+fn decode_glyphs(display: &DisplayDef, s: &str) -> Result<String, DecodeError> {
     let (all_digits, output) = s.split_once('|').unwrap();
-    let mut signals_1: Option<HashSet<char>> = None;
-    let mut signals_4: Option<HashSet<char>> = None;
-
-    for signals in all_digits.split_whitespace() {
-        match signals.len() {
-            2 => {
-                //digit 1: c,f
-                signals_1 = Some(signals.chars().collect());
+    let signals = all_digits.split_whitespace().collect::<Vec<_>>();
+    let outputs = output.split_whitespace().collect::<Vec<_>>();
+    let wiring = solve_wiring_with(display, &signals, &outputs)?;
+    output
+        .split_whitespace()
+        .map(|signals| decode_glyph(display, &wiring, signals))
+        .collect()
+}
+
+fn solve_wiring(signals: &[&str]) -> Result<HashMap<char, char>, DecodeError> {
+    solve_wiring_with(&DisplayDef::default(), signals, &[])
+}
+
+// Maps each wire to the segment it drives. Every signal pattern shows a
+// different glyph, so a repeated one can't come from a working display.
+fn solve_wiring_with(
+    display: &DisplayDef,
+    signals: &[&str],
+    outputs: &[&str],
+) -> Result<HashMap<char, char>, DecodeError> {
+    let to_masks = |patterns: &[&str]| {
+        patterns
+            .iter()
+            .map(|p| display.to_mask(p))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let signals = to_masks(signals)?;
+    if !all_distinct(&signals) {
+        return Err(DecodeError::Inconsistent);
+    }
+    let patterns = distinct_patterns(&signals, &to_masks(outputs)?);
+    let solutions = find_wirings(display, &patterns, None, 2);
+    match solutions.as_slice() {
+        [] => Err(DecodeError::Inconsistent),
+        [wiring] => Ok(named_wiring(display, wiring)),
+        _ => Err(DecodeError::Ambiguous),
+    }
+}
+
+fn all_distinct(patterns: &[u32]) -> bool {
+    patterns.iter().unique().count() == patterns.len()
+}
+
+// The signal patterns followed by the output patterns not already among them.
+fn distinct_patterns(signals: &[u32], outputs: &[u32]) -> Vec<u32> {
+    signals.iter().chain(outputs).copied().unique().collect()
+}
+
+fn named_wiring(display: &DisplayDef, wiring: &[usize]) -> HashMap<char, char> {
+    wiring
+        .iter()
+        .enumerate()
+        .map(|(wire, &segment)| (display.segments[wire], display.segments[segment]))
+        .collect()
+}
+
+// Up to `limit` wirings under which every pattern shows a glyph. A faulty
+// wire says nothing about its segment, so it is left out of every comparison.
+fn find_wirings(
+    display: &DisplayDef,
+    patterns: &[u32],
+    faulty: Option<usize>,
+    limit: usize,
+) -> Vec<Vec<usize>> {
+    let size = display.segments.len();
+    let all = ((1u64 << size) - 1) as u32;
+    let mut search = WiringSearch {
+        patterns,
+        glyphs: display.masks().collect(),
+        domains: vec![all; size],
+        faulty,
+        limit,
+        solutions: vec![],
+    };
+    // A wire lit in a pattern drives a segment of some glyph of that size;
+    // an unlit wire drives a segment missing from one.
+    for &pattern in patterns {
+        let fitting = display.masks().filter(|&g| search.fits_size(pattern, g));
+        let lit = fitting.clone().fold(0, |acc, g| acc | g);
+        let unlit = fitting.fold(0, |acc, g| acc | (!g & all));
+        for (wire, domain) in search.domains.iter_mut().enumerate() {
+            if Some(wire) != faulty {
+                *domain &= if pattern & 1 << wire != 0 { lit } else { unlit };
+            }
+        }
+    }
+    search.search(&mut vec![None; size]);
+    search.solutions
+}
+
+struct WiringSearch<'a> {
+    patterns: &'a [u32],
+    glyphs: Vec<u32>,
+    domains: Vec<u32>,
+    faulty: Option<usize>,
+    limit: usize,
+    solutions: Vec<Vec<usize>>,
+}
+
+impl WiringSearch<'_> {
+    // Backtracking over the wire with the fewest options left.
+    fn search(&mut self, assigned: &mut Vec<Option<usize>>) {
+        if self.solutions.len() >= self.limit {
+            return;
+        }
+        let used = assigned.iter().flatten().fold(0, |acc, s| acc | 1 << s);
+        let next = (0..assigned.len())
+            .filter(|&w| assigned[w].is_none())
+            .min_by_key(|&w| (self.domains[w] & !used).count_ones());
+        let wire = match next {
+            Some(wire) => wire,
+            None => {
+                self.solutions
+                    .push(assigned.iter().map(|s| s.unwrap()).collect());
+                return;
+            }
+        };
+        for segment in 0..assigned.len() {
+            if (self.domains[wire] & !used) & 1 << segment == 0 {
+                continue;
             }
-            4 => {
-                // digit 4: b,c,d,f
-                signals_4 = Some(signals.chars().collect());
+            assigned[wire] = Some(segment);
+            if self
+                .patterns
+                .iter()
+                .all(|&p| self.fits_some_glyph(p, assigned))
+            {
+                self.search(assigned);
             }
-            _ => (),
+            assigned[wire] = None;
         }
     }
 
-    let mut e_sig = None;
-    let mut c_sig = None;
-    let whole_set: HashSet<_> = ['a', 'b', 'c', 'd', 'e', 'f', 'g'].into();
-    for signals in all_digits.split_whitespace().filter(|s| s.len() == 6) {
-        let set: HashSet<_> = signals.chars().collect();
-        if set.is_superset(signals_1.as_ref().unwrap()) {
-            if set.is_superset(signals_4.as_ref().unwrap()) {
-                // Found 9; gives signal for segment e.
-                e_sig = whole_set.difference(&set).next().cloned();
+    fn fits_size(&self, pattern: u32, glyph: u32) -> bool {
+        match self.faulty {
+            None => glyph.count_ones() == pattern.count_ones(),
+            Some(f) => {
+                let known = (pattern & !(1 << f)).count_ones();
+                glyph.count_ones() == known || glyph.count_ones() == known + 1
             }
-            // Else found 0; gives segment d but not needed.
+        }
+    }
+
+    fn fits_some_glyph(&self, pattern: u32, assigned: &[Option<usize>]) -> bool {
+        self.glyphs
+            .iter()
+            .filter(|&&g| self.fits_size(pattern, g))
+            .any(|g| {
+                assigned
+                    .iter()
+                    .enumerate()
+                    .all(|(wire, segment)| match segment {
+                        _ if Some(wire) == self.faulty => true,
+                        Some(s) => (pattern & 1 << wire != 0) == (g & 1 << s != 0),
+                        None => true,
+                    })
+            })
+    }
+}
+
+fn decode_glyph(
+    display: &DisplayDef,
+    wiring: &HashMap<char, char>,
+    signals: &str,
+) -> Result<char, DecodeError> {
+    let segments = signals
+        .chars()
+        .map(|c| wiring.get(&c).copied().ok_or(DecodeError::UnknownWire(c)))
+        .collect::<Result<String, _>>()?;
+    let mask = display.to_mask(&segments)?;
+    display
+        .glyphs
+        .iter()
+        .find(|&&(_, g)| g == mask)
+        .map(|&(glyph, _)| glyph)
+        .ok_or(DecodeError::Inconsistent)
+}
+
+////////// Fault diagnosis
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fault {
+    StuckOn(char),
+    StuckOff(char),
+}
+
+#[derive(Debug)]
+struct Diagnosis {
+    // The simplest explanation: no fault if one fits, otherwise a single
+    // stuck wire.
+    fault: Option<Fault>,
+    wiring: HashMap<char, char>,
+    // How many (fault, wiring) pairs explain the patterns, healthy or not.
+    explanations: usize,
+    // Output glyphs that every explanation agrees on.
+    outputs: Vec<Option<char>>,
+}
+
+const MAX_EXPLANATIONS: usize = 10_000;
+
+fn diagnose(display: &DisplayDef, s: &str) -> Result<Diagnosis, DecodeError> {
+    let (all_digits, output) = s.split_once('|').unwrap();
+    let to_masks = |part: &str| {
+        part.split_whitespace()
+            .map(|p| display.to_mask(p))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let outputs = to_masks(output)?;
+    let signals = to_masks(all_digits)?;
+    let patterns = distinct_patterns(&signals, &outputs);
+    // Only a wire lit in every pattern can be stuck on, and only a wire lit
+    // in none can be stuck off.
+    let lit_in_all = patterns.iter().fold(u32::MAX, |acc, p| acc & p);
+    let lit_in_any = patterns.iter().fold(0, |acc, p| acc | p);
+    let faults = (0..display.segments.len()).filter_map(|wire| {
+        let name = display.segments[wire];
+        if lit_in_all & 1 << wire != 0 {
+            Some((wire, Fault::StuckOn(name)))
+        } else if lit_in_any & 1 << wire == 0 {
+            Some((wire, Fault::StuckOff(name)))
         } else {
-            // Found 6; gives signal for segment c.
-            c_sig = whole_set.difference(&set).next().cloned();
+            None
         }
+    });
+    // A healthy display shows distinct glyphs as distinct patterns, so a
+    // repeated signal pattern is itself evidence of a fault. Distinct ones
+    // then cover every glyph when all of them are given, as wirings are
+    // one-to-one.
+    let mut explanations = vec![];
+    if all_distinct(&signals) {
+        let healthy = find_wirings(display, &patterns, None, MAX_EXPLANATIONS);
+        explanations.extend(healthy.into_iter().map(|wiring| (None, wiring)));
+    }
+    // Faults are considered even when a healthy wiring fits, so an output only
+    // counts as known if no single stuck wire would change it.
+    for (wire, fault) in faults {
+        let found = find_wirings(display, &patterns, Some(wire), MAX_EXPLANATIONS);
+        explanations.extend(found.into_iter().map(|w| (Some((wire, fault)), w)));
     }
-    let e_sig = e_sig.unwrap();
-    let c_sig = c_sig.unwrap();
-    let mut result = String::new();
-    for signals in output.split_whitespace() {
-        match signals.len() {
-            2 => result.push('1'),
-            3 => result.push('7'),
-            4 => result.push('4'),
-            5 if signals.contains(e_sig) => result.push('2'),
-            5 if signals.contains(c_sig) => result.push('3'),
-            5 => result.push('5'),
-            6 if !signals.contains(e_sig) => result.push('9'),
-            6 if !signals.contains(c_sig) => result.push('6'),
-            6 => result.push('0'),
-            7 => result.push('8'),
-            _ => panic!(),
+    let (fault, wiring) = explanations.first().ok_or(DecodeError::Inconsistent)?;
+    let outputs = outputs
+        .iter()
+        .map(|&output| {
+            explanations
+                .iter()
+                .flat_map(|(fault, wiring)| {
+                    possible_glyphs(display, wiring, fault.map(|(w, _)| w), output)
+                })
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .exactly_one()
+                .ok()
+        })
+        .collect();
+    Ok(Diagnosis {
+        fault: fault.map(|(_, f)| f),
+        wiring: named_wiring(display, wiring),
+        explanations: explanations.len(),
+        outputs,
+    })
+}
+
+fn possible_glyphs(
+    display: &DisplayDef,
+    wiring: &[usize],
+    faulty: Option<usize>,
+    pattern: u32,
+) -> Vec<char> {
+    let mut segments = 0;
+    let mut unknown = 0;
+    for (wire, &segment) in wiring.iter().enumerate() {
+        if Some(wire) == faulty {
+            unknown |= 1 << segment;
+        } else if pattern & 1 << wire != 0 {
+            segments |= 1 << segment;
         }
     }
-    result.parse().unwrap()
+    display
+        .glyphs
+        .iter()
+        .filter(|&&(_, g)| g & !unknown == segments)
+        .map(|&(glyph, _)| glyph)
+        .collect()
 }
+
 #[cfg(test)]
 mod tests {
     use std::fs;
 
-    use crate::day08::{part_1, part_2};
+    use itertools::Itertools;
+
+    use crate::day08::{
+        decode_entry, decode_glyphs, diagnose, find_wirings, part_1, part_2, solve_wiring,
+        DecodeError, DisplayDef, Fault,
+    };
+    const EXAMPLE: &str =
+        "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf";
+    // Fourteen segments: the outer ring a-f, middle halves g and h, upper
+    // diagonals i and k around the vertical j, and lower diagonals l and n
+    // around the vertical m.
+    const FOURTEEN_SEGMENT: [(char, &str); 25] = [
+        ('0', "abcdefkn"),
+        ('1', "bck"),
+        ('2', "abdegh"),
+        ('3', "abcdh"),
+        ('4', "bcfgh"),
+        ('5', "acdfgh"),
+        ('6', "acdefgh"),
+        ('7', "abc"),
+        ('8', "abcdefgh"),
+        ('9', "abcdfgh"),
+        ('A', "abcefgh"),
+        ('B', "abcdhjm"),
+        ('C', "adef"),
+        ('D', "abcdjm"),
+        ('E', "adefg"),
+        ('F', "aefg"),
+        ('H', "bcefgh"),
+        ('I', "adjm"),
+        ('K', "efgkl"),
+        ('L', "def"),
+        ('M', "bcefik"),
+        ('N', "bcefil"),
+        ('X', "ikln"),
+        ('Y', "ikm"),
+        ('Z', "adkn"),
+    ];
     #[test]
     fn day08_part1() {
         let input = fs::read_to_string("input/day08").unwrap();
@@ -86,4 +435,116 @@ mod tests {
         let ans: usize = input.lines().map(part_2).sum();
         assert_eq!(ans, 1011823);
     }
+    #[test]
+    fn solve_example_wiring() {
+        let patterns = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab"
+            .split_whitespace()
+            .collect::<Vec<_>>();
+        let wiring = solve_wiring(&patterns).unwrap();
+        let wiring = "abcdefg".chars().map(|w| wiring[&w]).collect::<String>();
+        assert_eq!(wiring, "cfgabde");
+    }
+    #[test]
+    fn decode_errors() {
+        assert_eq!(decode_entry("ab | ba"), Err(DecodeError::Ambiguous));
+        assert_eq!(
+            decode_entry("ab abc abcd abcde | ab"),
+            Err(DecodeError::Inconsistent)
+        );
+        assert_eq!(decode_entry("ab | xy"), Err(DecodeError::UnknownWire('x')));
+    }
+    #[test]
+    fn fourteen_segment_display() {
+        let display = DisplayDef::new("abcdefghijklmn", &FOURTEEN_SEGMENT).unwrap();
+        let scramble = |lit: &str| {
+            lit.chars()
+                .map(|c| b"hnbkacmeifjgdl"[(c as u8 - b'a') as usize] as char)
+                .collect::<String>()
+        };
+        let patterns = FOURTEEN_SEGMENT.map(|(_, lit)| scramble(lit)).join(" ");
+        let output = "HELL0"
+            .chars()
+            .map(|g| scramble(FOURTEEN_SEGMENT.iter().find(|(c, _)| *c == g).unwrap().1))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let entry = format!("{patterns} | {output}");
+        assert_eq!(decode_glyphs(&display, &entry), Ok("HELL0".to_string()));
+        let seven = DisplayDef::default();
+        assert_eq!(decode_glyphs(&seven, EXAMPLE), Ok("5353".to_string()));
+    }
+    #[test]
+    fn diagnose_faults() {
+        let display = DisplayDef::default();
+        let healthy = diagnose(&display, EXAMPLE).unwrap();
+        assert_eq!(healthy.fault, None);
+        assert_eq!(healthy.explanations, 1);
+        assert_eq!(
+            healthy.outputs,
+            vec![Some('5'), Some('3'), Some('5'), Some('3')]
+        );
+
+        // Wire g drives segment e, the only difference between 5 and 6.
+        let stuck_off = EXAMPLE.replace('g', "");
+        let diagnosis = diagnose(&display, &stuck_off).unwrap();
+        assert_eq!(diagnosis.fault, Some(Fault::StuckOff('g')));
+        assert_eq!(diagnosis.wiring[&'d'], 'a');
+        assert_eq!(diagnosis.outputs, vec![None, Some('3'), None, Some('3')]);
+
+        let stuck_on = EXAMPLE
+            .split(' ')
+            .map(|p| {
+                if p == "|" {
+                    p.to_string()
+                } else {
+                    p.replace('g', "") + "g"
+                }
+            })
+            .join(" ");
+        let diagnosis = diagnose(&display, &stuck_on).unwrap();
+        assert_eq!(diagnosis.fault, Some(Fault::StuckOn('g')));
+        assert_eq!(diagnosis.outputs, vec![None, Some('3'), None, Some('3')]);
+
+        let broken = "ab bc ac abcdefg | ab";
+        assert_eq!(
+            diagnose(&display, broken).err(),
+            Some(DecodeError::Inconsistent)
+        );
+    }
+    #[test]
+    fn segment_limit() {
+        let segments: String = ('a'..='z').chain('A'..='F').collect();
+        // Glyph k lights the first k + 1 segments, so every glyph has its own size.
+        let glyphs: Vec<(char, String)> = segments
+            .char_indices()
+            .map(|(k, glyph)| (glyph, segments[..=k].to_string()))
+            .collect();
+        let glyphs: Vec<(char, &str)> = glyphs.iter().map(|(g, lit)| (*g, lit.as_str())).collect();
+        let display = DisplayDef::new(&segments, &glyphs).unwrap();
+        let patterns: Vec<u32> = display.masks().collect();
+        let wirings = find_wirings(&display, &patterns, None, 2);
+        assert_eq!(wirings, [(0..32).collect::<Vec<_>>()]);
+        let too_many = format!("{segments}G");
+        assert_eq!(
+            DisplayDef::new(&too_many, &[]).err(),
+            Some(DecodeError::TooManySegments(33))
+        );
+    }
+    #[test]
+    fn repeated_signals() {
+        // A wire stuck on makes two pairs of digits look alike, so signals repeat.
+        let entry =
+            "afg adefbg aefbg aecbg adcfbg defg fg adecfbg adecfbg adefbg | adecfbg fg afg adefbg";
+        assert_eq!(decode_entry(entry), Err(DecodeError::Inconsistent));
+        let signals = ["ab", "ab"];
+        assert_eq!(solve_wiring(&signals), Err(DecodeError::Inconsistent));
+    }
+    #[test]
+    fn diagnose_repeated_signals() {
+        // Really 8175 with a wire stuck on; a healthy wiring would read 8179.
+        let entry =
+            "afg adefbg aefbg aecbg adcfbg defg fg adecfbg adecfbg adefbg | adecfbg fg afg adefbg";
+        let diagnosis = diagnose(&DisplayDef::default(), entry).unwrap();
+        assert_eq!(diagnosis.fault, Some(Fault::StuckOn('g')));
+        assert_eq!(diagnosis.outputs, vec![None, Some('1'), Some('7'), None]);
+    }
 }
@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use crate::image::{Image, Palette, BLACK, WHITE};
+
 type Pos = (usize, usize);
 fn is_low_point(map: &[Vec<u32>], (x, y): Pos) -> bool {
     let center = map[y][x];
@@ -38,53 +40,120 @@ fn get_low_points(map: &[Vec<u32>]) -> Vec<Pos> {
     result
 }
 
-//// Part 2
+////////// Part 2
 
 fn get_basin_map(map: &[Vec<u32>]) -> Vec<Vec<usize>> {
-    let mut basin = vec![vec![0; map[0].len()]; map.len()];
-    // Fill in the low points
-    for (id, lp) in get_low_points(map).iter().enumerate() {
-        basin[lp.1][lp.0] = id + 1; // first id 1
-    }
-    // Fill in all points
-    for y in 0..basin.len() {
-        for x in 0..basin[0].len() {
-            if map[y][x] == 9 {
-                continue; // Not part of any basin
-            }
-            if basin[y][x] != 0 {
-                continue; // Filled in already
-            }
-            let mut stack = vec![(x, y)];
-            let mut lower = find_lower_point(map, (x, y));
-            while basin[lower.1][lower.0] == 0 {
-                stack.push(lower);
-                lower = find_lower_point(map, lower);
-            }
-            let basin_id = basin[lower.1][lower.0];
-            for p in stack {
-                basin[p.1][p.0] = basin_id;
+    label_basins(map, 9, Connectivity::Four).labels
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Connectivity {
+    Four,
+    Eight,
+}
+
+impl Connectivity {
+    // Neighbours already visited in a row-major scan.
+    fn earlier_neighbors(self, (x, y): Pos, width: usize) -> Vec<Pos> {
+        let mut result = vec![];
+        if x > 0 {
+            result.push((x - 1, y));
+        }
+        if y > 0 {
+            result.push((x, y - 1));
+            if self == Connectivity::Eight {
+                if x > 0 {
+                    result.push((x - 1, y - 1));
+                }
+                if x + 1 < width {
+                    result.push((x + 1, y - 1));
+                }
             }
         }
+        result
     }
-    basin
 }
 
-fn find_lower_point(map: &[Vec<u32>], (x, y): Pos) -> Pos {
-    let center = map[y][x];
-    if y != 0 && map[y - 1][x] < center {
-        return (x, y - 1);
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(size: usize) -> Self {
+        Self {
+            parent: (0..size).collect(),
+        }
     }
-    if x != 0 && map[y][x - 1] < center {
-        return (x - 1, y);
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut i = i;
+        while self.parent[i] != root {
+            let next = self.parent[i];
+            self.parent[i] = root;
+            i = next;
+        }
+        root
     }
-    if y != map.len() - 1 && map[y + 1][x] < center {
-        return (x, y + 1);
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parent[a.max(b)] = a.min(b);
     }
-    if x != map[0].len() - 1 && map[y][x + 1] < center {
-        return (x + 1, y);
+}
+
+struct Basins {
+    // Basin id per cell, numbered from 1 in row-major order; 0 marks a wall.
+    labels: Vec<Vec<usize>>,
+    // Indexed by id - 1.
+    sizes: Vec<usize>,
+    low_points: Vec<Pos>,
+}
+
+// Every connected region of cells below the wall height is one basin.
+fn label_basins(map: &[Vec<u32>], wall: u32, connectivity: Connectivity) -> Basins {
+    let width = map[0].len();
+    let index = |(x, y): Pos| y * width + x;
+    let mut sets = UnionFind::new(width * map.len());
+    for (y, row) in map.iter().enumerate() {
+        for (x, &height) in row.iter().enumerate() {
+            if height >= wall {
+                continue;
+            }
+            for (nx, ny) in connectivity.earlier_neighbors((x, y), width) {
+                if map[ny][nx] < wall {
+                    sets.union(index((x, y)), index((nx, ny)));
+                }
+            }
+        }
+    }
+    let mut ids = HashMap::new();
+    let mut basins = Basins {
+        labels: vec![vec![0; width]; map.len()],
+        sizes: vec![],
+        low_points: vec![],
+    };
+    for (y, row) in map.iter().enumerate() {
+        for (x, &height) in row.iter().enumerate() {
+            if height >= wall {
+                continue;
+            }
+            let root = sets.find(index((x, y)));
+            let id = *ids.entry(root).or_insert_with(|| {
+                basins.sizes.push(0);
+                basins.low_points.push((x, y));
+                basins.sizes.len()
+            });
+            basins.labels[y][x] = id;
+            basins.sizes[id - 1] += 1;
+            let (lx, ly) = basins.low_points[id - 1];
+            if height < map[ly][lx] {
+                basins.low_points[id - 1] = (x, y);
+            }
+        }
     }
-    (x, y)
+    basins
 }
 
 fn multiply_three_largest_basin_areas(basin_map: &[Vec<usize>]) -> u32 {
@@ -98,11 +167,201 @@ fn multiply_three_largest_basin_areas(basin_map: &[Vec<usize>]) -> u32 {
     basin_areas.iter().rev().take(3).product()
 }
 
+////////// Analysis
+
+struct Analysis {
+    // Minima and peaks are plateaus: connected cells of equal height whose
+    // neighbours are all higher, or all lower.
+    minima: Vec<Vec<Pos>>,
+    peaks: Vec<Vec<Pos>>,
+    saddles: Vec<Pos>,
+    // The minimum each cell drains to by steepest descent, numbered from 1
+    // in the order of `minima`.
+    watershed: Vec<Vec<usize>>,
+    catchment_sizes: Vec<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Summary {
+    minima: usize,
+    plateau_minima: usize,
+    peaks: usize,
+    saddles: usize,
+    largest_catchment: usize,
+}
+
+impl Analysis {
+    fn summary(&self) -> Summary {
+        Summary {
+            minima: self.minima.len(),
+            plateau_minima: self.minima.iter().filter(|m| m.len() > 1).count(),
+            peaks: self.peaks.len(),
+            saddles: self.saddles.len(),
+            largest_catchment: self.catchment_sizes.iter().copied().max().unwrap_or(0),
+        }
+    }
+}
+
+fn neighbors(map: &[Vec<u32>], (x, y): Pos) -> Vec<Pos> {
+    let mut result = vec![];
+    if y > 0 {
+        result.push((x, y - 1));
+    }
+    if x > 0 {
+        result.push((x - 1, y));
+    }
+    if y + 1 < map.len() {
+        result.push((x, y + 1));
+    }
+    if x + 1 < map[0].len() {
+        result.push((x + 1, y));
+    }
+    result
+}
+
+// The 8 surrounding heights in circular order change between higher and
+// lower than the centre at least four times.
+fn is_saddle(map: &[Vec<u32>], (x, y): Pos) -> bool {
+    if x == 0 || y == 0 || y + 1 == map.len() || x + 1 == map[0].len() {
+        return false;
+    }
+    let ring = [
+        (0, 0),
+        (1, 0),
+        (2, 0),
+        (2, 1),
+        (2, 2),
+        (1, 2),
+        (0, 2),
+        (0, 1),
+    ];
+    let center = map[y][x];
+    let signs = ring
+        .iter()
+        .map(|&(dx, dy)| map[y + dy - 1][x + dx - 1])
+        .filter(|&h| h != center)
+        .map(|h| h > center)
+        .collect::<Vec<_>>();
+    let changes = (0..signs.len())
+        .filter(|&i| signs[i] != signs[(i + 1) % signs.len()])
+        .count();
+    changes >= 4
+}
+
+fn analyse(map: &[Vec<u32>]) -> Analysis {
+    let width = map[0].len();
+    let index = |(x, y): Pos| y * width + x;
+    let mut sets = UnionFind::new(width * map.len());
+    for (y, row) in map.iter().enumerate() {
+        for (x, &height) in row.iter().enumerate() {
+            for (nx, ny) in Connectivity::Four.earlier_neighbors((x, y), width) {
+                if map[ny][nx] == height {
+                    sets.union(index((x, y)), index((nx, ny)));
+                }
+            }
+        }
+    }
+    let mut plateaus: HashMap<usize, Vec<Pos>> = HashMap::new();
+    for y in 0..map.len() {
+        for x in 0..width {
+            plateaus
+                .entry(sets.find(index((x, y))))
+                .or_default()
+                .push((x, y));
+        }
+    }
+    // Every plateau drains through its lowest strictly lower neighbour, with
+    // ties going to the first in row-major order.
+    let mut exits = HashMap::new();
+    let mut analysis = Analysis {
+        minima: vec![],
+        peaks: vec![],
+        saddles: vec![],
+        watershed: vec![vec![0; width]; map.len()],
+        catchment_sizes: vec![],
+    };
+    let mut roots = plateaus.keys().copied().collect::<Vec<_>>();
+    roots.sort_unstable();
+    for root in roots {
+        let cells = &plateaus[&root];
+        let (x, y) = cells[0];
+        let height = map[y][x];
+        let around = cells
+            .iter()
+            .flat_map(|&p| neighbors(map, p))
+            .filter(|&(nx, ny)| map[ny][nx] != height)
+            .collect::<Vec<_>>();
+        let exit = around
+            .iter()
+            .filter(|&&(nx, ny)| map[ny][nx] < height)
+            .min_by_key(|&&(nx, ny)| (map[ny][nx], ny, nx));
+        match exit {
+            Some(&exit) => {
+                exits.insert(root, sets.find(index(exit)));
+            }
+            None => {
+                analysis.minima.push(cells.clone());
+                exits.insert(root, root);
+            }
+        }
+        if !around.is_empty() && around.iter().all(|&(nx, ny)| map[ny][nx] < height) {
+            analysis.peaks.push(cells.clone());
+        }
+    }
+    let minimum_ids = analysis
+        .minima
+        .iter()
+        .enumerate()
+        .map(|(i, cells)| (sets.find(index(cells[0])), i + 1))
+        .collect::<HashMap<_, _>>();
+    analysis.catchment_sizes = vec![0; minimum_ids.len()];
+    for y in 0..map.len() {
+        for x in 0..width {
+            let mut plateau = sets.find(index((x, y)));
+            while exits[&plateau] != plateau {
+                plateau = exits[&plateau];
+            }
+            let id = minimum_ids[&plateau];
+            analysis.watershed[y][x] = id;
+            analysis.catchment_sizes[id - 1] += 1;
+            if is_saddle(map, (x, y)) {
+                analysis.saddles.push((x, y));
+            }
+        }
+    }
+    analysis
+}
+
+pub(crate) fn render(input: &str) -> Image {
+    let map: Vec<Vec<u32>> = input
+        .lines()
+        .map(|line| line.chars().map(|c| c.to_digit(10).unwrap()).collect())
+        .collect();
+    render_basins(&map)
+}
+
+fn render_basins(map: &[Vec<u32>]) -> Image {
+    let basins = label_basins(map, 9, Connectivity::Four);
+    let palette = Palette::distinct(basins.sizes.len());
+    let mut image = Image::from_grid(&basins.labels, |&id| match id {
+        0 => BLACK,
+        id => palette.color(id - 1),
+    });
+    for &(x, y) in &basins.low_points {
+        image.set(x, y, WHITE);
+    }
+    image
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
 
-    use crate::day09::{get_basin_map, multiply_three_largest_basin_areas};
+    use crate::day09::{
+        analyse, get_basin_map, label_basins, multiply_three_largest_basin_areas, render_basins,
+        Connectivity, Summary,
+    };
+    use crate::image::{BLACK, WHITE};
 
     use super::sum_risk_levels;
     #[test]
@@ -147,4 +406,84 @@ mod tests {
         let ans = multiply_three_largest_basin_areas(&basin_map);
         assert_eq!(ans, 891684);
     }
+    #[test]
+    fn example09_basins() {
+        let input = fs::read_to_string("input/example09").unwrap();
+        let height_map: Vec<Vec<u32>> = input
+            .lines()
+            .map(|line| line.chars().map(|c| c.to_digit(10).unwrap()).collect())
+            .collect();
+        let basins = label_basins(&height_map, 9, Connectivity::Four);
+        assert_eq!(basins.sizes, vec![3, 9, 14, 9]);
+        assert_eq!(basins.low_points, vec![(1, 0), (9, 0), (2, 2), (6, 4)]);
+        let basins = label_basins(&height_map, 9, Connectivity::Eight);
+        assert_eq!(basins.sizes, vec![35]);
+        let basins = label_basins(&height_map, 8, Connectivity::Four);
+        assert_eq!(basins.sizes.iter().sum::<usize>(), 25);
+    }
+    #[test]
+    fn plateau_basin() {
+        let height_map = vec![vec![5, 5, 9, 2], vec![5, 5, 9, 2], vec![9, 9, 9, 3]];
+        let basins = label_basins(&height_map, 9, Connectivity::Four);
+        assert_eq!(
+            basins.labels,
+            vec![vec![1, 1, 0, 2], vec![1, 1, 0, 2], vec![0, 0, 0, 2]]
+        );
+        assert_eq!(basins.sizes, vec![4, 3]);
+        assert_eq!(basins.low_points, vec![(0, 0), (3, 0)]);
+    }
+    #[test]
+    fn example09_analysis() {
+        let input = fs::read_to_string("input/example09").unwrap();
+        let height_map: Vec<Vec<u32>> = input
+            .lines()
+            .map(|line| line.chars().map(|c| c.to_digit(10).unwrap()).collect())
+            .collect();
+        let analysis = analyse(&height_map);
+        let minima = analysis.minima.iter().map(|m| m[0]).collect::<Vec<_>>();
+        assert_eq!(minima, vec![(1, 0), (9, 0), (2, 2), (6, 4)]);
+        assert_eq!(analysis.catchment_sizes.iter().sum::<usize>(), 50);
+    }
+    #[test]
+    fn plateau_and_saddle_analysis() {
+        let height_map = vec![
+            vec![9, 9, 9, 9, 9],
+            vec![9, 1, 5, 1, 9],
+            vec![9, 9, 9, 9, 9],
+        ];
+        let analysis = analyse(&height_map);
+        assert_eq!(analysis.saddles, vec![(2, 1)]);
+        assert_eq!(analysis.catchment_sizes, vec![14, 1]);
+        assert_eq!(analysis.peaks.len(), 1);
+
+        let height_map = vec![vec![3, 3, 4], vec![3, 3, 4], vec![4, 4, 2]];
+        let analysis = analyse(&height_map);
+        assert_eq!(
+            analysis.watershed,
+            vec![vec![1, 1, 2], vec![1, 1, 2], vec![2, 2, 2]]
+        );
+        assert_eq!(
+            analysis.summary(),
+            Summary {
+                minima: 2,
+                plateau_minima: 1,
+                peaks: 2,
+                saddles: 0,
+                largest_catchment: 5,
+            }
+        );
+    }
+    #[test]
+    fn example09_render() {
+        let input = fs::read_to_string("input/example09").unwrap();
+        let height_map: Vec<Vec<u32>> = input
+            .lines()
+            .map(|line| line.chars().map(|c| c.to_digit(10).unwrap()).collect())
+            .collect();
+        let image = render_basins(&height_map);
+        assert_eq!(image.get(1, 0), WHITE);
+        assert_eq!(image.get(2, 0), BLACK);
+        assert_eq!(image.get(0, 0), image.get(0, 1));
+        assert_ne!(image.get(0, 0), image.get(5, 0));
+    }
 }
@@ -1,43 +1,304 @@
-use std::collections::HashMap;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    io::{self, BufRead},
+};
 
+#[derive(Debug, PartialEq, Eq)]
 enum SyntaxResult {
     Valid,
     Incomplete(Vec<char>),
-    Error(char),
+    Error(SyntaxError),
 }
 
-fn check_syntax(line: &str) -> SyntaxResult {
-    let mut stack = vec![];
-    for c in line.chars() {
-        match c {
-            '(' | '[' | '{' | '<' => stack.push(c),
-            ')' | ']' | '}' | '>' => match (stack.pop(), c) {
-                (Some('('), ')') | (Some('['), ']') | (Some('{'), '}') | (Some('<'), '>') => (),
-                _ => return SyntaxResult::Error(c),
-            },
-            _ => panic!(),
+#[derive(Debug, PartialEq, Eq)]
+enum SyntaxError {
+    // A closer that doesn't match the innermost open delimiter. `expected` and
+    // `opened_at` are None when nothing was open.
+    Mismatch {
+        position: usize,
+        found: char,
+        expected: Option<char>,
+        opened_at: Option<usize>,
+    },
+    // A character that is neither an opener nor a closer.
+    Stray {
+        position: usize,
+        found: char,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StrayPolicy {
+    Panic,
+    Skip,
+    Report,
+}
+
+struct Checker {
+    pairs: Vec<(char, char)>,
+    stray: StrayPolicy,
+}
+
+impl Default for Checker {
+    fn default() -> Self {
+        Checker::new(&[('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')])
+    }
+}
+
+impl Checker {
+    fn new(pairs: &[(char, char)]) -> Self {
+        Checker {
+            pairs: pairs.to_vec(),
+            stray: StrayPolicy::Panic,
         }
     }
-    if !stack.is_empty() {
-        return SyntaxResult::Incomplete(stack);
+
+    fn with_stray(self, stray: StrayPolicy) -> Self {
+        Checker { stray, ..self }
     }
-    SyntaxResult::Valid
-}
 
-fn part_1(input: &str) -> u32 {
-    let error_score: HashMap<char, u32> = [(')', 3), (']', 57), ('}', 1197), ('>', 25137)].into();
-    input
-        .lines()
-        .map(check_syntax)
-        .filter_map(|res| {
-            if let SyntaxResult::Error(c) = res {
-                Some(c)
+    fn closer(&self, open: char) -> Option<char> {
+        self.pairs
+            .iter()
+            .find(|&&(o, _)| o == open)
+            .map(|&(_, c)| c)
+    }
+
+    fn is_closer(&self, c: char) -> bool {
+        self.opener(c).is_some()
+    }
+
+    fn opener(&self, close: char) -> Option<char> {
+        self.pairs
+            .iter()
+            .find(|&&(_, c)| c == close)
+            .map(|&(o, _)| o)
+    }
+
+    fn check(&self, line: &str) -> SyntaxResult {
+        let mut stack: Vec<(char, usize)> = vec![];
+        for (position, c) in line.chars().enumerate() {
+            // Closing comes first so that a pair like ('"', '"') can close.
+            let top = stack.last().map(|&(open, _)| open);
+            if top.is_some_and(|open| self.closer(open) == Some(c)) {
+                stack.pop();
+            } else if self.closer(c).is_some() {
+                stack.push((c, position));
+            } else if self.is_closer(c) {
+                let open = stack.pop();
+                return SyntaxResult::Error(SyntaxError::Mismatch {
+                    position,
+                    found: c,
+                    expected: open.and_then(|(o, _)| self.closer(o)),
+                    opened_at: open.map(|(_, at)| at),
+                });
             } else {
-                None
+                match self.stray {
+                    StrayPolicy::Panic => panic!("unexpected {c:?} at position {position}"),
+                    StrayPolicy::Skip => (),
+                    StrayPolicy::Report => {
+                        return SyntaxResult::Error(SyntaxError::Stray { position, found: c })
+                    }
+                }
+            }
+        }
+        if !stack.is_empty() {
+            return SyntaxResult::Incomplete(stack.into_iter().map(|(open, _)| open).collect());
+        }
+        SyntaxResult::Valid
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    // Positions index the characters of the original line; an insertion goes
+    // before the character at `position`.
+    Insert {
+        position: usize,
+        inserted: char,
+    },
+    Delete {
+        position: usize,
+        deleted: char,
+    },
+    Replace {
+        position: usize,
+        found: char,
+        with: char,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Repair {
+    line: String,
+    edits: Vec<Edit>,
+}
+
+#[derive(Clone, Copy)]
+enum Choice {
+    Empty,
+    Keep,
+    Delete,
+    // Insert the closer for the first character before position k.
+    Insert(usize),
+    // Pair the first character with the one at position k.
+    Pair(usize),
+}
+
+impl Checker {
+    // The cheapest set of single-character edits that balances the line,
+    // found by interval DP over cost[i][j], the cost of balancing chars[i..j].
+    // Stray characters are kept when the policy skips them, deleted otherwise.
+    fn repair(&self, line: &str) -> Repair {
+        let chars: Vec<char> = line.chars().collect();
+        let n = chars.len();
+        let mut cost = vec![vec![0; n + 1]; n + 1];
+        let mut choice = vec![vec![Choice::Empty; n + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in i + 1..=n {
+                // The first character of a segment can't close anything inside
+                // it, so one that both opens and closes is taken as an opener.
+                let c = chars[i];
+                let stray = self.closer(c).is_none() && !self.is_closer(c);
+                let mut best = (u32::MAX, Choice::Empty);
+                if stray && self.stray == StrayPolicy::Skip {
+                    best = (cost[i + 1][j], Choice::Keep);
+                }
+                if let Some(close) = self.closer(c) {
+                    for (k, candidate) in (i + 1..=j).map(|k| (k, 1 + cost[i + 1][k] + cost[k][j]))
+                    {
+                        // Ties close as late as possible, like a completion.
+                        if candidate <= best.0 {
+                            best = (candidate, Choice::Insert(k));
+                        }
+                    }
+                    for k in i + 1..j {
+                        let replace = u32::from(chars[k] != close);
+                        let candidate = replace + cost[i + 1][k] + cost[k + 1][j];
+                        if candidate < best.0 {
+                            best = (candidate, Choice::Pair(k));
+                        }
+                    }
+                } else if !stray {
+                    for k in (i + 1..j).filter(|&k| self.is_closer(chars[k])) {
+                        let candidate = 1 + cost[i + 1][k] + cost[k + 1][j];
+                        if candidate < best.0 {
+                            best = (candidate, Choice::Pair(k));
+                        }
+                    }
+                }
+                // Deleting comes last so that ties favour completing the line.
+                if 1 + cost[i + 1][j] < best.0 {
+                    best = (1 + cost[i + 1][j], Choice::Delete);
+                }
+                (cost[i][j], choice[i][j]) = best;
+            }
+        }
+        let mut repair = Repair {
+            line: String::with_capacity(n),
+            edits: vec![],
+        };
+        self.rebuild(&chars, &choice, 0, n, &mut repair);
+        repair
+    }
+
+    fn rebuild(
+        &self,
+        chars: &[char],
+        choice: &[Vec<Choice>],
+        i: usize,
+        j: usize,
+        repair: &mut Repair,
+    ) {
+        if i == j {
+            return;
+        }
+        let c = chars[i];
+        match choice[i][j] {
+            Choice::Empty => (),
+            Choice::Keep => {
+                repair.line.push(c);
+                self.rebuild(chars, choice, i + 1, j, repair);
             }
-        })
-        .map(|c| error_score[&c])
-        .sum()
+            Choice::Delete => {
+                repair.edits.push(Edit::Delete {
+                    position: i,
+                    deleted: c,
+                });
+                self.rebuild(chars, choice, i + 1, j, repair);
+            }
+            Choice::Insert(k) => {
+                let close = self.closer(c).unwrap();
+                repair.line.push(c);
+                self.rebuild(chars, choice, i + 1, k, repair);
+                repair.line.push(close);
+                repair.edits.push(Edit::Insert {
+                    position: k,
+                    inserted: close,
+                });
+                self.rebuild(chars, choice, k, j, repair);
+            }
+            Choice::Pair(k) => {
+                let (open, close) = match self.closer(c) {
+                    Some(close) => (c, close),
+                    None => (self.opener(chars[k]).unwrap(), chars[k]),
+                };
+                if open != c {
+                    repair.edits.push(Edit::Replace {
+                        position: i,
+                        found: c,
+                        with: open,
+                    });
+                }
+                repair.line.push(open);
+                self.rebuild(chars, choice, i + 1, k, repair);
+                if close != chars[k] {
+                    repair.edits.push(Edit::Replace {
+                        position: k,
+                        found: chars[k],
+                        with: close,
+                    });
+                }
+                repair.line.push(close);
+                self.rebuild(chars, choice, k + 1, j, repair);
+            }
+        }
+    }
+}
+
+// Repairs every line of a file, keeping valid lines as they are.
+pub(crate) fn repair_lines(input: &str) -> (String, usize) {
+    let checker = Checker::default().with_stray(StrayPolicy::Report);
+    let mut edits = 0;
+    let mut output = String::with_capacity(input.len());
+    for line in input.lines() {
+        let repair = checker.repair(line);
+        edits += repair.edits.len();
+        output.push_str(&repair.line);
+        output.push('\n');
+    }
+    (output, edits)
+}
+
+fn check_syntax(line: &str) -> SyntaxResult {
+    Checker::default().check(line)
+}
+
+fn error_score(c: char) -> u64 {
+    match c {
+        ')' => 3,
+        ']' => 57,
+        '}' => 1197,
+        '>' => 25137,
+        _ => 0,
+    }
+}
+
+fn part_1(input: &str) -> u64 {
+    check_stream(input.as_bytes(), &Checker::default())
+        .unwrap()
+        .corruption_score
 }
 
 fn calculate_completion_score(to_close: &[char]) -> u64 {
@@ -50,27 +311,96 @@ fn calculate_completion_score(to_close: &[char]) -> u64 {
 }
 
 fn part_2(input: &str) -> u64 {
-    let mut completion_scores: Vec<u64> = input
-        .lines()
-        .map(check_syntax)
-        .filter_map(|res| {
-            if let SyntaxResult::Incomplete(v) = res {
-                Some(v)
-            } else {
-                None
+    check_stream(input.as_bytes(), &Checker::default())
+        .unwrap()
+        .completion_scores
+        .median()
+        .unwrap()
+}
+
+// Keeps the smaller half of the values in a max-heap and the larger half in a
+// min-heap, so the median is always on top of one of them.
+#[derive(Default)]
+struct StreamingMedian {
+    lower: BinaryHeap<u64>,
+    upper: BinaryHeap<Reverse<u64>>,
+}
+
+impl StreamingMedian {
+    fn push(&mut self, value: u64) {
+        match self.upper.peek() {
+            Some(&Reverse(min)) if value < min => self.lower.push(value),
+            _ => self.upper.push(Reverse(value)),
+        }
+        // The upper half holds the extra value when the count is odd.
+        if self.lower.len() > self.upper.len() {
+            self.upper.push(Reverse(self.lower.pop().unwrap()));
+        } else if self.upper.len() > self.lower.len() + 1 {
+            self.lower.push(self.upper.pop().unwrap().0);
+        }
+    }
+
+    // The value at index len / 2 in sorted order, as `part_2` always used.
+    fn median(&self) -> Option<u64> {
+        self.upper.peek().map(|&Reverse(median)| median)
+    }
+
+    fn len(&self) -> usize {
+        self.lower.len() + self.upper.len()
+    }
+}
+
+#[derive(Default)]
+struct SyntaxStats {
+    valid: usize,
+    incomplete: usize,
+    corrupted: usize,
+    stray: usize,
+    corruption_score: u64,
+    completion_scores: StreamingMedian,
+}
+
+impl SyntaxStats {
+    fn record(&mut self, result: &SyntaxResult) {
+        match result {
+            SyntaxResult::Valid => self.valid += 1,
+            SyntaxResult::Incomplete(open) => {
+                self.incomplete += 1;
+                self.completion_scores
+                    .push(calculate_completion_score(open));
             }
-        })
-        .map(|v| calculate_completion_score(&v))
-        .collect();
-    let middle = completion_scores.len() / 2;
-    *completion_scores.select_nth_unstable(middle).1
+            SyntaxResult::Error(SyntaxError::Mismatch { found, .. }) => {
+                self.corrupted += 1;
+                self.corruption_score += error_score(*found);
+            }
+            SyntaxResult::Error(SyntaxError::Stray { .. }) => self.stray += 1,
+        }
+    }
+}
+
+// Checks one line at a time, so only the current line and the running totals
+// are ever held in memory.
+fn check_stream(mut reader: impl BufRead, checker: &Checker) -> io::Result<SyntaxStats> {
+    let mut stats = SyntaxStats::default();
+    let mut line = String::new();
+    while reader.read_line(&mut line)? != 0 {
+        stats.record(&checker.check(line.trim_end_matches(['\r', '\n'])));
+        line.clear();
+    }
+    Ok(stats)
 }
 
 #[cfg(test)]
 mod tests {
-    use std::fs;
+    use std::{
+        fs::{self, File},
+        io::BufReader,
+    };
 
-    use super::{part_1, part_2};
+    use super::{
+        check_stream, part_1, part_2, repair_lines, Checker, Edit, StrayPolicy, StreamingMedian,
+        SyntaxError, SyntaxResult,
+    };
     #[test]
     fn example10_part1() {
         let input = fs::read_to_string("input/example10").unwrap();
@@ -91,4 +421,120 @@ mod tests {
         let input = fs::read_to_string("input/day10").unwrap();
         assert_eq!(part_2(&input), 2192104158);
     }
+    #[test]
+    fn error_positions() {
+        let checker = Checker::default();
+        assert_eq!(
+            checker.check("{([(<{}[<>[]}>{[]{[(<()>"),
+            SyntaxResult::Error(SyntaxError::Mismatch {
+                position: 12,
+                found: '}',
+                expected: Some(']'),
+                opened_at: Some(7),
+            })
+        );
+        assert_eq!(
+            checker.check("()>"),
+            SyntaxResult::Error(SyntaxError::Mismatch {
+                position: 2,
+                found: '>',
+                expected: None,
+                opened_at: None,
+            })
+        );
+        assert_eq!(checker.check("[<>"), SyntaxResult::Incomplete(vec!['[']));
+    }
+    #[test]
+    fn configurable_pairs() {
+        let quotes = Checker::new(&[('«', '»'), ('/', '\\')]);
+        assert_eq!(quotes.check("«/\\»"), SyntaxResult::Valid);
+        assert_eq!(quotes.check("«/"), SyntaxResult::Incomplete(vec!['«', '/']));
+        let skipping = Checker::default().with_stray(StrayPolicy::Skip);
+        assert_eq!(skipping.check("(a [b] c)"), SyntaxResult::Valid);
+        let reporting = Checker::default().with_stray(StrayPolicy::Report);
+        assert_eq!(
+            reporting.check("(a)"),
+            SyntaxResult::Error(SyntaxError::Stray {
+                position: 1,
+                found: 'a'
+            })
+        );
+    }
+    #[test]
+    fn same_character_pairs() {
+        let bars = Checker::new(&[('|', '|')]);
+        assert_eq!(bars.check("||"), SyntaxResult::Valid);
+        assert_eq!(bars.check("|"), SyntaxResult::Incomplete(vec!['|']));
+        let quotes = Checker::new(&[('(', ')'), ('"', '"')]);
+        assert_eq!(quotes.check("(\"\")\"()\""), SyntaxResult::Valid);
+        assert_eq!(
+            quotes.check("(\")"),
+            SyntaxResult::Error(SyntaxError::Mismatch {
+                position: 2,
+                found: ')',
+                expected: Some('"'),
+                opened_at: Some(1),
+            })
+        );
+        assert_eq!(bars.repair("|").line, "||");
+        assert!(bars.repair("||").edits.is_empty());
+        assert_eq!(quotes.repair("(\")").edits.len(), 1);
+        assert_eq!(
+            quotes.check(&quotes.repair("(\")").line),
+            SyntaxResult::Valid
+        );
+    }
+    #[test]
+    fn minimal_repairs() {
+        let checker = Checker::default();
+        let repair = checker.repair("(]");
+        assert_eq!(repair.line, "()");
+        assert_eq!(
+            repair.edits,
+            [Edit::Replace {
+                position: 1,
+                found: ']',
+                with: ')'
+            }]
+        );
+        assert_eq!(checker.repair("((").edits.len(), 1);
+        assert_eq!(checker.repair("([)").edits.len(), 1);
+        assert_eq!(checker.repair(")(").edits.len(), 2);
+        assert_eq!(checker.repair("{()").line, "{()}");
+        let reporting = Checker::default().with_stray(StrayPolicy::Report);
+        assert_eq!(reporting.repair("(x)").line, "()");
+        let skipping = Checker::default().with_stray(StrayPolicy::Skip);
+        assert_eq!(skipping.repair("(x").line, "(x)");
+    }
+    #[test]
+    fn example10_repair() {
+        let input = fs::read_to_string("input/example10").unwrap();
+        let checker = Checker::default();
+        let (repaired, _) = repair_lines(&input);
+        for (line, fixed) in input.lines().zip(repaired.lines()) {
+            assert_eq!(checker.check(fixed), SyntaxResult::Valid);
+            if let SyntaxResult::Incomplete(open) = checker.check(line) {
+                assert!(checker.repair(line).edits.len() <= open.len());
+            }
+        }
+    }
+    #[test]
+    fn streaming_median() {
+        let mut median = StreamingMedian::default();
+        assert_eq!(median.median(), None);
+        for (value, expected) in [(5, 5), (1, 5), (9, 5), (3, 5), (2, 3), (8, 5)] {
+            median.push(value);
+            assert_eq!(median.median(), Some(expected));
+        }
+        assert_eq!(median.len(), 6);
+    }
+    #[test]
+    fn example10_stream() {
+        let file = BufReader::new(File::open("input/example10").unwrap());
+        let checker = Checker::default().with_stray(StrayPolicy::Report);
+        let stats = check_stream(file, &checker).unwrap();
+        assert_eq!((stats.valid, stats.incomplete, stats.corrupted), (0, 5, 5));
+        assert_eq!(stats.corruption_score, 26397);
+        assert_eq!(stats.completion_scores.median(), Some(288957));
+    }
 }
@@ -1,20 +1,48 @@
 use itertools::Itertools;
-use std::collections::HashSet;
-fn simulate_flashing_octos(input: &str, steps: u32) -> (u64, Option<u32>) {
-    let mut octo_map: Vec<Vec<u8>> = input
-        .lines()
-        .map(|l| l.chars().map(|c| c.to_digit(10).unwrap() as u8).collect())
-        .collect();
-    // Results
-    let mut flash_count = 0;
-    let mut steps_to_first_all_flash = None;
-    // Run the sim
-    for turn in 0..steps {
-        octo_map
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    iter,
+};
+
+use crate::image::{heat, Image};
+
+type Pos = (usize, usize);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct OctopusGrid {
+    energy: Vec<Vec<u8>>,
+}
+
+impl OctopusGrid {
+    fn parse(input: &str) -> Self {
+        let energy = input
+            .lines()
+            .map(|l| l.chars().map(|c| c.to_digit(10).unwrap() as u8).collect())
+            .collect();
+        OctopusGrid { energy }
+    }
+
+    fn width(&self) -> usize {
+        self.energy.first().map_or(0, Vec::len)
+    }
+
+    fn height(&self) -> usize {
+        self.energy.len()
+    }
+
+    fn len(&self) -> usize {
+        self.width() * self.height()
+    }
+
+    // Advances one step and returns the octopuses that flashed during it.
+    fn step(&mut self) -> HashSet<Pos> {
+        let (max_x, max_y) = (self.width() - 1, self.height() - 1);
+        self.energy
             .iter_mut()
             .flatten()
             .for_each(|octopus| *octopus += 1);
-        let mut will_flash: Vec<(usize, usize)> = octo_map
+        let mut will_flash: Vec<Pos> = self
+            .energy
             .iter()
             .enumerate()
             .flat_map(|(y, row)| {
@@ -24,33 +52,135 @@ fn simulate_flashing_octos(input: &str, steps: u32) -> (u64, Option<u32>) {
                     .map(move |(x, _)| (x, y))
             })
             .collect();
-        let mut has_flashed = HashSet::<(usize, usize)>::new();
+        let mut has_flashed = HashSet::<Pos>::new();
         while let Some(pos) = will_flash.pop() {
             if has_flashed.contains(&pos) {
                 continue;
             }
             has_flashed.insert(pos);
-            flash_count += 1;
-            for neighbor in get_neighbors(pos, 9, 9) {
+            for neighbor in get_neighbors(pos, max_x, max_y) {
                 if has_flashed.contains(&neighbor) {
                     continue;
                 }
-                let neighbor_octo: &mut u8 = &mut octo_map[neighbor.1][neighbor.0];
+                let neighbor_octo: &mut u8 = &mut self.energy[neighbor.1][neighbor.0];
                 *neighbor_octo += 1;
                 if *neighbor_octo > 9 {
                     will_flash.push(neighbor);
                 }
             }
         }
-        has_flashed.iter().for_each(|&(x, y)| octo_map[y][x] = 0);
-        if has_flashed.len() == 100 && steps_to_first_all_flash.is_none() {
-            steps_to_first_all_flash = Some(turn + 1);
+        has_flashed.iter().for_each(|&(x, y)| self.energy[y][x] = 0);
+        has_flashed
+    }
+
+    // The flashes of every step from here on, forever.
+    fn steps(mut self) -> impl Iterator<Item = HashSet<Pos>> {
+        iter::repeat_with(move || self.step())
+    }
+}
+
+fn simulate_flashing_octos(input: &str, steps: u32) -> (u64, Option<u32>) {
+    let grid = OctopusGrid::parse(input);
+    let size = grid.len();
+    let mut flash_count = 0;
+    let mut steps_to_first_all_flash = None;
+    for (turn, flashed) in (1..=steps).zip(grid.steps()) {
+        flash_count += flashed.len() as u64;
+        if flashed.len() == size && steps_to_first_all_flash.is_none() {
+            steps_to_first_all_flash = Some(turn);
         }
     }
     (flash_count, steps_to_first_all_flash)
 }
 
-fn get_neighbors((x, y): (usize, usize), max_x: usize, max_y: usize) -> Vec<(usize, usize)> {
+fn part_1(input: &str) -> u64 {
+    simulate_flashing_octos(input, 100).0
+}
+
+// Runs until the grid repeats a state, with None if the octopuses never all
+// flash together or no state repeats within CYCLE_BUDGET steps.
+fn part_2(input: &str) -> Option<usize> {
+    find_cycle(OctopusGrid::parse(input), Some(CYCLE_BUDGET))?.first_synchronised()
+}
+
+// The flash counts of a simulation that has been run until a grid state
+// repeated: steps 1..=pre_period lead into the cycle, after which the next
+// `period` steps repeat forever.
+struct Cycle {
+    pre_period: usize,
+    period: usize,
+    // flashes[i] is the number of flashes during step i + 1.
+    flashes: Vec<usize>,
+    size: usize,
+}
+
+impl Cycle {
+    fn flashes_during(&self, step: u64) -> usize {
+        self.flashes[self.index(step)]
+    }
+
+    // Total flashes over the first `steps` steps.
+    fn flashes_after(&self, steps: u64) -> u64 {
+        let sum = |range: std::ops::Range<usize>| self.flashes[range].iter().sum::<usize>() as u64;
+        let pre_period = self.pre_period as u64;
+        if steps <= pre_period {
+            return sum(0..steps as usize);
+        }
+        let (cycles, rest) = (
+            (steps - pre_period) / self.period as u64,
+            (steps - pre_period) % self.period as u64,
+        );
+        sum(0..self.pre_period)
+            + cycles * sum(self.pre_period..self.flashes.len())
+            + sum(self.pre_period..self.pre_period + rest as usize)
+    }
+
+    // The first step on which every octopus flashes, if that ever happens.
+    fn first_synchronised(&self) -> Option<usize> {
+        self.flashes
+            .iter()
+            .position(|&f| f == self.size)
+            .map(|i| i + 1)
+    }
+
+    fn index(&self, step: u64) -> usize {
+        let (step, pre_period) = (step - 1, self.pre_period as u64);
+        if step < pre_period {
+            step as usize
+        } else {
+            self.pre_period + ((step - pre_period) % self.period as u64) as usize
+        }
+    }
+}
+
+// How many steps part 2 simulates before giving up on finding a cycle.
+const CYCLE_BUDGET: usize = 100_000;
+
+// Steps until a grid state repeats, or None if that takes more than budget
+// steps. The number of states is finite but can be astronomically large, so
+// without a budget this may run for a very long time and hold every state.
+// Once the grid synchronises the period is ten.
+fn find_cycle(mut grid: OctopusGrid, budget: Option<usize>) -> Option<Cycle> {
+    let size = grid.len();
+    let mut seen = HashMap::new();
+    let mut flashes = vec![];
+    while let Entry::Vacant(entry) = seen.entry(grid.clone()) {
+        if budget.is_some_and(|budget| flashes.len() >= budget) {
+            return None;
+        }
+        entry.insert(flashes.len());
+        flashes.push(grid.step().len());
+    }
+    let pre_period = seen[&grid];
+    Some(Cycle {
+        pre_period,
+        period: flashes.len() - pre_period,
+        flashes,
+        size,
+    })
+}
+
+fn get_neighbors((x, y): Pos, max_x: usize, max_y: usize) -> Vec<Pos> {
     // TODO: Faster with i8?
     let x_vals = [x as i64 - 1, x as i64, x as i64 + 1];
     let y_vals = [y as i64 - 1, y as i64, y as i64 + 1];
@@ -66,11 +196,20 @@ fn get_neighbors((x, y): (usize, usize), max_x: usize, max_y: usize) -> Vec<(usi
         .collect()
 }
 
+pub(crate) fn render(input: &str) -> Image {
+    render_energy(&OctopusGrid::parse(input).energy)
+}
+
+fn render_energy(octo_map: &[Vec<u8>]) -> Image {
+    Image::from_grid(octo_map, |&energy| heat(energy as u32, 9))
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
 
-    use super::simulate_flashing_octos;
+    use super::{find_cycle, part_1, part_2, render_energy, simulate_flashing_octos, OctopusGrid};
+    use crate::image::heat;
     #[test]
     fn example11_part1() {
         let input = fs::read_to_string("input/example11").unwrap();
@@ -95,4 +234,55 @@ mod tests {
         let ans = simulate_flashing_octos(&input, 500);
         assert_eq!(ans.1, Some(312));
     }
+    #[test]
+    fn example11_render() {
+        let input = fs::read_to_string("input/example11").unwrap();
+        let image = render_energy(&OctopusGrid::parse(&input).energy);
+        assert_eq!(image.get(0, 0), heat(5, 9));
+        assert_eq!(image.get(3, 0), heat(3, 9));
+    }
+    #[test]
+    fn day11_parts() {
+        let input = fs::read_to_string("input/day11").unwrap();
+        assert_eq!(part_1(&input), 1640);
+        assert_eq!(part_2(&input), Some(312));
+        for never in ["05", "05\n50", "00000\n55555"] {
+            assert_eq!(part_2(never), None);
+        }
+    }
+    #[test]
+    fn other_sizes() {
+        let mut grid = OctopusGrid::parse("11111\n19991\n19191\n19991\n11111\n");
+        assert_eq!(grid.step().len(), 9);
+        assert_eq!(
+            grid.energy,
+            OctopusGrid::parse("34543\n40004\n50005\n40004\n34543\n").energy
+        );
+        assert!(grid.step().is_empty());
+        let row = OctopusGrid::parse("989\n");
+        assert_eq!((row.width(), row.height()), (3, 1));
+        let flashes: Vec<usize> = row.steps().take(3).map(|flashed| flashed.len()).collect();
+        assert_eq!(flashes, [3, 0, 0]);
+    }
+    #[test]
+    fn example11_cycle() {
+        let input = fs::read_to_string("input/example11").unwrap();
+        let grid = OctopusGrid::parse(&input);
+        assert!(find_cycle(grid.clone(), Some(200)).is_none());
+        assert!(find_cycle(grid.clone(), Some(205)).is_some());
+        let cycle = find_cycle(grid, None).unwrap();
+        assert_eq!((cycle.pre_period, cycle.period), (195, 10));
+        assert_eq!(cycle.first_synchronised(), Some(195));
+        assert_eq!(cycle.flashes_after(100), 1656);
+        assert_eq!(
+            cycle.flashes_after(195 + 10 * 7),
+            cycle.flashes_after(195) + 7 * 100
+        );
+        assert_eq!(cycle.flashes_during(195), 100);
+        assert_eq!(cycle.flashes_during(195 + 10_u64.pow(12)), 100);
+        assert_eq!(cycle.flashes_during(196 + 10_u64.pow(12)), 0);
+        let steps = 1000;
+        let simulated = simulate_flashing_octos(&input, steps).0;
+        assert_eq!(cycle.flashes_after(steps as u64), simulated);
+    }
 }
@@ -1,4 +1,4 @@
-use im::HashSet;
+use itertools::Itertools;
 use std::collections::HashMap;
 
 type CaveMap = HashMap<String, Vec<String>>;
@@ -18,73 +18,350 @@ fn read_cave_map(input: &str) -> CaveMap {
     result
 }
 
-fn path_search(
-    cave: &str,
-    mut visited_small_caves: HashSet<String>,
-    map: &CaveMap,
-    path_count: &mut u32,
-    mut extra_small_visit: bool,
-) {
-    if cave == "end" {
-        *path_count += 1;
-        return;
-    }
-
-    if cave.chars().all(|c| c.is_ascii_lowercase()) {
-        // small cave
-        if visited_small_caves.contains(cave) {
-            if extra_small_visit {
-                extra_small_visit = false;
-            } else {
-                return;
+#[derive(Debug, PartialEq, Eq)]
+enum CaveMapError {
+    MissingCave(String),
+    DuplicateEdge(String, String),
+    // Two connected caves without a visit limit, which allow endless paths.
+    AdjacentBigCaves(String, String),
+    // More visit counts than fit in the bits of a CaveGraph key.
+    TooManyLimitedCaves,
+}
+
+// Checks that the map has both endpoints of the policy, no edge listed twice
+// and no two unlimited caves other than the endpoints next to each other,
+// reporting the first problem.
+fn validate(map: &CaveMap, policy: &VisitPolicy) -> Result<(), CaveMapError> {
+    for endpoint in [&policy.start, &policy.end] {
+        if !map.contains_key(endpoint) {
+            return Err(CaveMapError::MissingCave(endpoint.clone()));
+        }
+    }
+    let edges = sorted_edges(map);
+    if let Some((a, b)) = edges
+        .iter()
+        .tuple_windows()
+        .find_map(|(e, f)| (e == f).then_some(e))
+    {
+        return Err(CaveMapError::DuplicateEdge(a.to_string(), b.to_string()));
+    }
+    // Paths never return to start or go on from end, so those can be big.
+    let big =
+        |cave: &str| policy.limit(cave).is_none() && cave != policy.start && cave != policy.end;
+    if let Some((a, b)) = edges.iter().find(|(a, b)| big(a) && big(b)) {
+        return Err(CaveMapError::AdjacentBigCaves(a.to_string(), b.to_string()));
+    }
+    Ok(())
+}
+
+// Every edge once, as a sorted pair of cave names, in sorted order. An edge
+// given twice in the input shows up twice.
+fn sorted_edges(map: &CaveMap) -> Vec<(&str, &str)> {
+    let mut edges: Vec<(&str, &str)> = map
+        .iter()
+        .flat_map(|(a, neighbors)| neighbors.iter().map(move |b| (a.as_str(), b.as_str())))
+        .filter(|(a, b)| a <= b)
+        .collect();
+    edges.sort_unstable();
+    // A loop from a cave to itself is listed twice among its own neighbors.
+    edges.dedup_by(|e, f| e == f && e.0 == e.1);
+    edges
+}
+
+// Graphviz source for the map: endpoints as double circles, small caves as
+// circles and big caves as filled boxes.
+fn to_dot(map: &CaveMap, policy: &VisitPolicy) -> String {
+    let mut caves: Vec<&str> = map.keys().map(String::as_str).collect();
+    caves.sort_unstable();
+    let mut dot = String::from("graph caves {\n");
+    for cave in caves {
+        let style = if cave == policy.start || cave == policy.end {
+            "shape=doublecircle"
+        } else if policy.limit(cave).is_some() {
+            "shape=circle"
+        } else {
+            "shape=box, style=filled, fillcolor=lightblue"
+        };
+        dot += &format!("    \"{cave}\" [{style}];\n");
+    }
+    for (a, b) in sorted_edges(map) {
+        dot += &format!("    \"{a}\" -- \"{b}\";\n");
+    }
+    dot + "}\n"
+}
+
+fn is_small(cave: &str) -> bool {
+    cave.chars().all(|c| c.is_ascii_lowercase())
+}
+
+// The rules a path has to follow. Small caves may be visited once and big
+// caves any number of times, unless a cave is given its own limit. On top of
+// that, up to `revisits` limited caves may each be visited `extra_visits`
+// more times than their limit. A path never returns to the start cave.
+struct VisitPolicy {
+    start: String,
+    end: String,
+    is_small: fn(&str) -> bool,
+    limits: HashMap<String, u32>,
+    revisits: usize,
+    extra_visits: u32,
+}
+
+impl Default for VisitPolicy {
+    fn default() -> Self {
+        VisitPolicy {
+            start: "start".to_string(),
+            end: "end".to_string(),
+            is_small,
+            limits: HashMap::new(),
+            revisits: 0,
+            extra_visits: 1,
+        }
+    }
+}
+
+impl VisitPolicy {
+    fn with_endpoints(self, start: &str, end: &str) -> Self {
+        VisitPolicy {
+            start: start.to_string(),
+            end: end.to_string(),
+            ..self
+        }
+    }
+
+    fn with_classifier(self, is_small: fn(&str) -> bool) -> Self {
+        VisitPolicy { is_small, ..self }
+    }
+
+    fn with_limit(mut self, cave: &str, limit: u32) -> Self {
+        self.limits.insert(cave.to_string(), limit);
+        self
+    }
+
+    fn with_revisits(self, revisits: usize, extra_visits: u32) -> Self {
+        VisitPolicy {
+            revisits,
+            extra_visits,
+            ..self
+        }
+    }
+
+    // How often a cave may be visited without using a revisit, or None for no
+    // limit at all.
+    fn limit(&self, cave: &str) -> Option<u32> {
+        match self.limits.get(cave) {
+            Some(&limit) => Some(limit),
+            None if (self.is_small)(cave) => Some(1),
+            None => None,
+        }
+    }
+
+    // Whether a cave already visited `count` times may be entered again, and
+    // if so whether that uses up one of the revisits.
+    fn admits(&self, limit: u32, count: u32, revisits_used: usize) -> Option<bool> {
+        if count < limit || (limit < count && count < limit + self.extra_visits) {
+            Some(false)
+        } else if count == limit && self.extra_visits > 0 && revisits_used < self.revisits {
+            Some(true)
+        } else {
+            None
+        }
+    }
+}
+
+// Every path from start to end, found depth first and produced one at a time.
+// Each partial path on the stack remembers how many revisits it has used.
+struct Routes<'a> {
+    map: &'a CaveMap,
+    policy: &'a VisitPolicy,
+    stack: Vec<(Vec<&'a str>, usize)>,
+    max_len: Option<usize>,
+    through: Vec<&'a str>,
+}
+
+fn routes<'a>(map: &'a CaveMap, policy: &'a VisitPolicy) -> Result<Routes<'a>, CaveMapError> {
+    validate(map, policy)?;
+    Ok(Routes {
+        map,
+        policy,
+        stack: vec![(vec![policy.start.as_str()], 0)],
+        max_len: None,
+        through: vec![],
+    })
+}
+
+impl<'a> Routes<'a> {
+    // Only paths of at most `max_len` caves, counting start and end.
+    fn max_len(self, max_len: usize) -> Self {
+        Routes {
+            max_len: Some(max_len),
+            ..self
+        }
+    }
+
+    // Only paths that visit `cave` at least once.
+    fn through(mut self, cave: &'a str) -> Self {
+        self.through.push(cave);
+        self
+    }
+}
+
+impl<'a> Iterator for Routes<'a> {
+    type Item = Vec<&'a str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let policy = self.policy;
+        while let Some((path, revisits_used)) = self.stack.pop() {
+            let cave = *path.last().unwrap();
+            if cave == policy.end {
+                if self.through.iter().all(|required| path.contains(required)) {
+                    return Some(path);
+                }
+                continue;
+            }
+            if self.max_len.is_some_and(|max_len| path.len() >= max_len) {
+                continue;
+            }
+            // Pushed in reverse so that routes come out in map order.
+            let neighbors = self.map.get(cave).into_iter().flatten().rev();
+            for next in neighbors.filter(|&next| *next != policy.start) {
+                let mut revisits_used = revisits_used;
+                if let Some(limit) = policy.limit(next) {
+                    let count = path.iter().filter(|&&c| c == next).count() as u32;
+                    match policy.admits(limit, count, revisits_used) {
+                        Some(revisit) => revisits_used += usize::from(revisit),
+                        None => continue,
+                    }
+                }
+                let mut next_path = path.clone();
+                next_path.push(next);
+                self.stack.push((next_path, revisits_used));
             }
         }
-        visited_small_caves.insert(cave.to_string());
-    }
-    for next in map[cave].iter().filter(|&neighbor| neighbor != "start") {
-        path_search(
-            next,
-            visited_small_caves.clone(),
-            map,
-            path_count,
-            extra_small_visit,
-        );
+        None
+    }
+}
+
+// The cave map with caves interned to dense ids, and the visit counts of all
+// limited caves packed into the bit fields of a u128. With the puzzle's rules
+// each field is a single bit, so the counts are just a visited set.
+struct CaveGraph<'a> {
+    policy: &'a VisitPolicy,
+    neighbors: Vec<Vec<usize>>,
+    // The limit and bit offset of each limited cave.
+    fields: Vec<Option<(u32, u32)>>,
+    width: u32,
+    start: usize,
+    end: usize,
+}
+
+impl<'a> CaveGraph<'a> {
+    fn new(map: &CaveMap, policy: &'a VisitPolicy) -> Result<Self, CaveMapError> {
+        validate(map, policy)?;
+        let names: Vec<&str> = map.keys().map(String::as_str).collect();
+        let id = |name: &str| names.iter().position(|&n| n == name).unwrap();
+        let max_count = names
+            .iter()
+            .filter_map(|&name| policy.limit(name))
+            .map(|limit| {
+                limit
+                    + if policy.revisits > 0 {
+                        policy.extra_visits
+                    } else {
+                        0
+                    }
+            })
+            .max()
+            .unwrap_or(0);
+        let width = u32::BITS - max_count.leading_zeros();
+        let mut offset = 0;
+        let fields = names
+            .iter()
+            .map(|&name| {
+                let limit = policy.limit(name)?;
+                let field = (limit, offset);
+                offset += width;
+                Some(field)
+            })
+            .collect();
+        if offset > u128::BITS {
+            return Err(CaveMapError::TooManyLimitedCaves);
+        }
+        let neighbors = names
+            .iter()
+            .map(|&name| map[name].iter().map(|n| id(n)).collect())
+            .collect();
+        Ok(CaveGraph {
+            policy,
+            neighbors,
+            fields,
+            width,
+            start: id(&policy.start),
+            end: id(&policy.end),
+        })
+    }
+
+    fn count_paths(&self) -> u64 {
+        let mut memo = HashMap::new();
+        self.count_from(self.start, 0, 0, &mut memo)
+    }
+
+    // Paths from `cave` to end, given the visit counts so far and the number
+    // of revisits used.
+    fn count_from(
+        &self,
+        cave: usize,
+        counts: u128,
+        revisits_used: usize,
+        memo: &mut HashMap<(usize, u128, usize), u64>,
+    ) -> u64 {
+        if cave == self.end {
+            return 1;
+        }
+        if let Some(&count) = memo.get(&(cave, counts, revisits_used)) {
+            return count;
+        }
+        let mask = (1 << self.width) - 1;
+        let mut paths = 0;
+        for &next in self.neighbors[cave].iter().filter(|&&n| n != self.start) {
+            match self.fields[next] {
+                None => paths += self.count_from(next, counts, revisits_used, memo),
+                Some((limit, offset)) => {
+                    let count = (counts >> offset & mask) as u32;
+                    if let Some(revisit) = self.policy.admits(limit, count, revisits_used) {
+                        let counts = counts + (1 << offset);
+                        let revisits_used = revisits_used + usize::from(revisit);
+                        paths += self.count_from(next, counts, revisits_used, memo);
+                    }
+                }
+            }
+        }
+        memo.insert((cave, counts, revisits_used), paths);
+        paths
     }
 }
 
-fn part_1(input: &str) -> u32 {
-    let map = read_cave_map(input);
-    let mut path_count = 0;
-    let extra_small_visit = false;
-    path_search(
-        "start",
-        HashSet::new(),
-        &map,
-        &mut path_count,
-        extra_small_visit,
-    );
-    path_count
+fn part_1(input: &str) -> u64 {
+    CaveGraph::new(&read_cave_map(input), &VisitPolicy::default())
+        .unwrap()
+        .count_paths()
 }
 
-fn part_2(input: &str) -> u32 {
-    let map = read_cave_map(input);
-    let mut path_count = 0;
-    let extra_small_visit = true;
-    path_search(
-        "start",
-        HashSet::new(),
-        &map,
-        &mut path_count,
-        extra_small_visit,
-    );
-    path_count
+fn part_2(input: &str) -> u64 {
+    let policy = VisitPolicy::default().with_revisits(1, 1);
+    CaveGraph::new(&read_cave_map(input), &policy)
+        .unwrap()
+        .count_paths()
 }
 
 #[cfg(test)]
 mod tests {
     use std::fs;
 
-    use super::{part_1, part_2};
+    use super::{
+        part_1, part_2, read_cave_map, routes, to_dot, validate, CaveGraph, CaveMapError,
+        VisitPolicy,
+    };
     #[test]
     fn example12_part1() {
         let input = fs::read_to_string("input/example12").unwrap();
@@ -104,4 +381,158 @@ mod tests {
         let ans = part_2(&input);
         assert_eq!(ans, 94849);
     }
+    #[test]
+    fn example12_routes() {
+        let input = fs::read_to_string("input/example12").unwrap();
+        let map = read_cave_map(&input);
+        let (part_1, part_2) = (
+            VisitPolicy::default(),
+            VisitPolicy::default().with_revisits(1, 1),
+        );
+        let mut all: Vec<String> = routes(&map, &part_1)
+            .unwrap()
+            .map(|path| path.join(","))
+            .collect();
+        all.sort();
+        assert_eq!(
+            all,
+            [
+                "start,A,b,A,c,A,end",
+                "start,A,b,A,end",
+                "start,A,b,end",
+                "start,A,c,A,b,A,end",
+                "start,A,c,A,b,end",
+                "start,A,c,A,end",
+                "start,A,end",
+                "start,b,A,c,A,end",
+                "start,b,A,end",
+                "start,b,end",
+            ]
+        );
+        assert_eq!(routes(&map, &part_1).unwrap().through("c").count(), 5);
+        assert_eq!(routes(&map, &part_1).unwrap().max_len(4).count(), 4);
+        assert_eq!(
+            routes(&map, &part_1)
+                .unwrap()
+                .through("c")
+                .max_len(6)
+                .count(),
+            3
+        );
+        assert_eq!(routes(&map, &part_2).unwrap().count(), 36);
+        assert!(routes(&map, &part_2).unwrap().all(|path| path
+            .iter()
+            .filter(|&&c| c == "d")
+            .count()
+            <= 2));
+    }
+    #[test]
+    fn many_small_caves() {
+        let mut input = String::from("start-A\nA-end\n");
+        for cave in "abcdefghijkl".chars() {
+            input += &format!("A-{cave}\n");
+        }
+        let map = read_cave_map(&input);
+        let part_1 = VisitPolicy::default();
+        // Any ordered selection of the twelve small caves, each visited via A.
+        assert_eq!(
+            CaveGraph::new(&map, &part_1).unwrap().count_paths(),
+            1302061345
+        );
+        assert_eq!(
+            routes(&map, &part_1).unwrap().max_len(7).count(),
+            1 + 12 + 12 * 11
+        );
+    }
+    #[test]
+    fn visit_policies() {
+        let input = fs::read_to_string("input/example12").unwrap();
+        let map = read_cave_map(&input);
+        let count = |policy: &VisitPolicy| {
+            let counted = CaveGraph::new(&map, policy).unwrap().count_paths();
+            assert_eq!(counted, routes(&map, policy).unwrap().count() as u64);
+            counted
+        };
+        assert_eq!(count(&VisitPolicy::default()), 10);
+        assert_eq!(count(&VisitPolicy::default().with_revisits(1, 1)), 36);
+        assert_eq!(count(&VisitPolicy::default().with_limit("b", 2)), 30);
+        assert_eq!(count(&VisitPolicy::default().with_revisits(2, 1)), 54);
+        assert_eq!(count(&VisitPolicy::default().with_limit("A", 2)), 8);
+        let reversed = VisitPolicy::default().with_endpoints("end", "start");
+        assert_eq!(count(&reversed), 10);
+        // Everything small, so A can only be passed once.
+        let all_small = VisitPolicy::default().with_classifier(|_| true);
+        assert_eq!(count(&all_small), 4);
+    }
+    #[test]
+    fn invalid_maps() {
+        let policy = VisitPolicy::default();
+        let check = |input: &str| validate(&read_cave_map(input), &policy);
+        assert_eq!(
+            check(&fs::read_to_string("input/example12").unwrap()),
+            Ok(())
+        );
+        assert_eq!(
+            check("start-A\nA-b\n"),
+            Err(CaveMapError::MissingCave("end".to_string()))
+        );
+        assert_eq!(
+            check("start-A\nA-end\nend-A\n"),
+            Err(CaveMapError::DuplicateEdge(
+                "A".to_string(),
+                "end".to_string()
+            ))
+        );
+        assert_eq!(
+            check("start-A\nA-B\nB-end\n"),
+            Err(CaveMapError::AdjacentBigCaves(
+                "A".to_string(),
+                "B".to_string()
+            ))
+        );
+        let map = read_cave_map("start-A\nA-B\nB-end\n");
+        assert!(CaveGraph::new(&map, &policy).is_err());
+        let crowded: String = (0..70)
+            .map(|i| format!("A-{}{}\n", (b'a' + i / 26) as char, (b'a' + i % 26) as char))
+            .collect();
+        let map = read_cave_map(&format!("start-A\nA-end\n{crowded}"));
+        assert_eq!(
+            CaveGraph::new(&map, &VisitPolicy::default().with_revisits(1, 1)).err(),
+            Some(CaveMapError::TooManyLimitedCaves)
+        );
+        assert!(CaveGraph::new(&map, &VisitPolicy::default().with_limit("B", 3)).is_ok());
+    }
+    #[test]
+    fn example12_dot() {
+        let map = read_cave_map("start-A\nA-b\nA-end\n");
+        assert_eq!(
+            to_dot(&map, &VisitPolicy::default()),
+            "graph caves {
+    \"A\" [shape=box, style=filled, fillcolor=lightblue];
+    \"b\" [shape=circle];
+    \"end\" [shape=doublecircle];
+    \"start\" [shape=doublecircle];
+    \"A\" -- \"b\";
+    \"A\" -- \"end\";
+    \"A\" -- \"start\";
+}
+"
+        );
+    }
+    #[test]
+    fn custom_endpoints() {
+        let map = read_cave_map("START-A\nA-b\nA-END\nb-END\n");
+        let policy = VisitPolicy::default().with_endpoints("START", "END");
+        assert_eq!(validate(&map, &policy), Ok(()));
+        assert_eq!(CaveGraph::new(&map, &policy).unwrap().count_paths(), 3);
+        assert_eq!(routes(&map, &policy).unwrap().count(), 3);
+        let looping = read_cave_map("start-A\nA-B\nB-end\n");
+        assert_eq!(
+            routes(&looping, &VisitPolicy::default()).err(),
+            Some(CaveMapError::AdjacentBigCaves(
+                "A".to_string(),
+                "B".to_string()
+            ))
+        );
+    }
 }
@@ -1,4 +1,8 @@
-#[derive(Clone, Copy)]
+use std::collections::HashMap;
+
+type Pos = (i64, i64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum FoldInstruction {
     X(i64),
     Y(i64),
@@ -15,8 +19,8 @@ impl FoldInstruction {
     }
 }
 fn parse(input: &str) -> (Vec<(i64, i64)>, Vec<FoldInstruction>) {
-    let empty_line = "\r\n\r\n";
-    let (dots, instructions) = input.split_once(empty_line).unwrap();
+    let input = input.replace("\r\n", "\n");
+    let (dots, instructions) = input.split_once("\n\n").unwrap();
     let dots: Vec<(i64, i64)> = dots
         .lines()
         .map(|l| {
@@ -28,90 +32,296 @@ fn parse(input: &str) -> (Vec<(i64, i64)>, Vec<FoldInstruction>) {
     (dots, instructions)
 }
 
-fn fold_dots(dots: &mut [(i64, i64)], fold: FoldInstruction) {
-    match fold {
-        FoldInstruction::X(axis) => dots
-            .iter_mut()
-            .for_each(|dot| *dot = (axis - (axis - dot.0).abs(), dot.1)),
-        FoldInstruction::Y(axis) => dots
-            .iter_mut()
-            .for_each(|dot| *dot = (dot.0, axis - (axis - dot.1).abs())),
-    }
+#[derive(Debug, PartialEq, Eq)]
+enum FoldError {
+    // The fold line would have to pass through this dot.
+    DotOnLine(Pos),
+    OutsideSheet(FoldInstruction),
 }
 
-fn draw_dots(mut dots: Vec<(i64, i64)>) {
-    dots.sort_unstable_by_key(|(x, _)| *x);
-    dots.sort_by_key(|(_, y)| *y);
-    let mut prev_x = 0;
-    let mut prev_y = 0;
-    let mut line = String::new();
-    for dot in dots {
-        if prev_y != dot.1 {
-            // new line
-            println!("{}", line);
-            line.clear();
-            for _ in (prev_y + 1)..dot.1 {
-                println!();
-            }
-            prev_y = dot.1;
+type Dots = HashMap<Pos, Vec<Pos>>;
+
+// A sheet of transparent paper. Every dot on the folded sheet remembers the
+// original dots that ended up on it, and every fold remembers the sheet as it
+// was before, so folds can be undone.
+struct Paper {
+    dots: Dots,
+    size: Pos,
+    history: Vec<(FoldInstruction, Dots, Pos)>,
+}
+
+impl Paper {
+    // The sheet is just big enough to hold the furthest dot.
+    fn new(dots: &[Pos]) -> Self {
+        Paper::with_size(dots, (0, 0))
+    }
+
+    // A sheet of the given size, grown where a dot lies beyond it.
+    fn with_size(dots: &[Pos], size: Pos) -> Self {
+        let mut paper = Paper {
+            dots: HashMap::new(),
+            size,
+            history: vec![],
+        };
+        for &(x, y) in dots {
+            paper.dots.insert((x, y), vec![(x, y)]);
+            paper.size = (paper.size.0.max(x + 1), paper.size.1.max(y + 1));
         }
+        paper
+    }
+
+    // The puzzle's folds halve the sheet, so the first fold along each axis
+    // gives the size along it, even when no dot reaches the far edge.
+    fn for_folds(dots: &[Pos], folds: &[FoldInstruction]) -> Self {
+        let first = |along_x: bool| {
+            folds.iter().find_map(|&fold| match fold {
+                FoldInstruction::X(axis) if along_x => Some(2 * axis + 1),
+                FoldInstruction::Y(axis) if !along_x => Some(2 * axis + 1),
+                _ => None,
+            })
+        };
+        Paper::with_size(dots, (first(true).unwrap_or(0), first(false).unwrap_or(0)))
+    }
+
+    fn len(&self) -> usize {
+        self.dots.len()
+    }
+
+    fn size(&self) -> Pos {
+        self.size
+    }
+
+    // The size of the sheet before each fold and as it is now.
+    fn sizes(&self) -> impl Iterator<Item = Pos> + '_ {
+        self.history
+            .iter()
+            .map(|&(_, _, size)| size)
+            .chain([self.size])
+    }
+
+    fn dots(&self) -> Vec<Pos> {
+        let mut dots: Vec<Pos> = self.dots.keys().copied().collect();
+        dots.sort_unstable();
+        dots
+    }
 
-        for _ in (prev_x + 1)..dot.0 {
-            line.push(' ');
+    // The original dots that were folded onto `dot`.
+    fn origins(&self, dot: Pos) -> &[Pos] {
+        self.dots.get(&dot).map_or(&[], Vec::as_slice)
+    }
+
+    // Folds the far side over the near one. When the far side is the longer
+    // one, the sheet shifts so that its coordinates still start at zero.
+    fn fold(&mut self, fold: FoldInstruction) -> Result<(), FoldError> {
+        let (axis, length) = match fold {
+            FoldInstruction::X(axis) => (axis, self.size.0),
+            FoldInstruction::Y(axis) => (axis, self.size.1),
+        };
+        if axis < 0 || axis >= length {
+            return Err(FoldError::OutsideSheet(fold));
+        }
+        let along = |(x, y): Pos| match fold {
+            FoldInstruction::X(_) => x,
+            FoldInstruction::Y(_) => y,
+        };
+        if let Some(&dot) = self.dots.keys().find(|&&dot| along(dot) == axis) {
+            return Err(FoldError::DotOnLine(dot));
+        }
+        let folded_length = axis.max(length - 1 - axis);
+        let shift = folded_length - axis;
+        let folded = |c: i64| axis - (axis - c).abs() + shift;
+        let mut dots: Dots = HashMap::with_capacity(self.dots.len());
+        for (&(x, y), origins) in &self.dots {
+            let dot = match fold {
+                FoldInstruction::X(_) => (folded(x), y),
+                FoldInstruction::Y(_) => (x, folded(y)),
+            };
+            dots.entry(dot).or_default().extend(origins);
         }
-        line.push('#');
-        prev_x = dot.0;
+        let size = match fold {
+            FoldInstruction::X(_) => (folded_length, self.size.1),
+            FoldInstruction::Y(_) => (self.size.0, folded_length),
+        };
+        let previous = std::mem::replace(&mut self.dots, dots);
+        self.history.push((fold, previous, self.size));
+        self.size = size;
+        Ok(())
+    }
+
+    // Undoes the last fold and returns it, if there was one.
+    fn unfold(&mut self) -> Option<FoldInstruction> {
+        let (fold, dots, size) = self.history.pop()?;
+        self.dots = dots;
+        self.size = size;
+        Some(fold)
     }
-    println!("{}", line);
+}
+
+fn part_1(input: &str) -> usize {
+    let (dots, instructions) = parse(input);
+    let mut paper = Paper::for_folds(&dots, &instructions);
+    paper.fold(instructions[0]).unwrap();
+    paper.len()
+}
+
+// Rows of '#' for dots and '.' for blank paper, covering the whole sheet.
+fn render_dots(paper: &Paper) -> String {
+    let (width, height) = paper.size();
+    let mut grid = vec![vec!['.'; width as usize]; height as usize];
+    for (x, y) in paper.dots() {
+        grid[y as usize][x as usize] = '#';
+    }
+    grid.into_iter()
+        .map(|row| row.into_iter().collect::<String>() + "\n")
+        .collect()
+}
+
+// The capital letters of the puzzle font, 4 wide and 6 tall, row after row.
+const FONT: [(char, &str); 17] = [
+    ('A', ".##.#..##..######..##..#"),
+    ('B', "###.#..####.#..##..####."),
+    ('C', ".##.#..##...#...#..#.##."),
+    ('E', "#####...###.#...#...####"),
+    ('F', "#####...###.#...#...#..."),
+    ('G', ".##.#..##...#.###..#.###"),
+    ('H', "#..##..######..##..##..#"),
+    ('I', ".###..#...#...#...#..###"),
+    ('J', "..##...#...#...##..#.##."),
+    ('K', "#..##.#.##..#.#.#.#.#..#"),
+    ('L', "#...#...#...#...#...####"),
+    ('O', ".##.#..##..##..##..#.##."),
+    ('P', "###.#..##..####.#...#..."),
+    ('R', "###.#..##..####.#.#.#..#"),
+    ('S', ".####...#....##....####."),
+    ('U', "#..##..##..##..##..#.##."),
+    ('Z', "####...#..#..#..#...####"),
+];
+
+// Reads letters spaced one column apart from a rendering. Anything that isn't
+// a letter of the font comes out as '?'.
+fn ocr(rendered: &str) -> String {
+    let rows: Vec<Vec<char>> = rendered.lines().map(|l| l.chars().collect()).collect();
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let pixel = |x: usize, y: usize| {
+        rows.get(y)
+            .and_then(|row| row.get(x))
+            .copied()
+            .unwrap_or('.')
+    };
+    (0..width.div_ceil(5))
+        .map(|letter| {
+            let glyph: String = (0..6)
+                .flat_map(|y| (0..4).map(move |x| (letter * 5 + x, y)))
+                .map(|(x, y)| pixel(x, y))
+                .collect();
+            FONT.iter()
+                .find(|&&(_, g)| g == glyph)
+                .map_or('?', |&(c, _)| c)
+        })
+        .collect()
+}
+
+fn part_2(input: &str) -> String {
+    let (dots, instructions) = parse(input);
+    let mut paper = Paper::for_folds(&dots, &instructions);
+    for &fold in &instructions {
+        paper.fold(fold).unwrap();
+    }
+    ocr(&render_dots(&paper))
 }
 
 #[cfg(test)]
 mod tests {
     use std::fs;
 
-    use crate::day13::FoldInstruction;
-
-    use super::{draw_dots, fold_dots, parse};
+    use super::{ocr, parse, part_1, part_2, render_dots, FoldError, FoldInstruction, Paper};
     #[test]
     fn example13_part1() {
         let input = fs::read_to_string("input/example13").unwrap();
-        let (mut dots, _) = parse(&input);
-        fold_dots(&mut dots, FoldInstruction::Y(7));
-        dots.sort_unstable();
-        dots.dedup();
-        assert_eq!(dots.len(), 17);
+        assert_eq!(part_1(&input), 17);
     }
     #[test]
     fn day13_part1() {
         let input = fs::read_to_string("input/day13").unwrap();
-        let (mut dots, _) = parse(&input);
-        fold_dots(&mut dots, FoldInstruction::X(655));
-        dots.sort_unstable();
-        dots.dedup();
-        assert_eq!(dots.len(), 664);
+        assert_eq!(part_1(&input), 664);
     }
     #[test]
     fn example13_part2() {
         let input = fs::read_to_string("input/example13").unwrap();
-        let (mut dots, instructions) = parse(&input);
-        instructions
-            .iter()
-            .for_each(|ins| fold_dots(&mut dots, *ins));
-        dots.sort_unstable();
-        dots.dedup();
-        draw_dots(dots);
-        // TODO: Dump drawing to file "expected_example13", then assert_eq in test
+        let (dots, instructions) = parse(&input);
+        let mut paper = Paper::for_folds(&dots, &instructions);
+        for &fold in &instructions {
+            paper.fold(fold).unwrap();
+        }
+        let rendered = render_dots(&paper);
+        assert_eq!(
+            rendered,
+            "#####\n#...#\n#...#\n#...#\n#####\n.....\n.....\n"
+        );
+        assert_eq!(ocr(&rendered), "?");
     }
     #[test]
     fn day13_part2() {
         let input = fs::read_to_string("input/day13").unwrap();
-        let (mut dots, instructions) = parse(&input);
-        instructions
-            .iter()
-            .for_each(|ins| fold_dots(&mut dots, *ins));
-        dots.sort_unstable();
-        dots.dedup();
-        draw_dots(dots);
-        // TODO: Dump drawing to file "expected_day13", then assert_eq in test
+        assert_eq!(part_2(&input), "EFJKZLBL");
+    }
+    #[test]
+    fn example13_paper() {
+        let input = fs::read_to_string("input/example13").unwrap();
+        let (dots, instructions) = parse(&input);
+        let mut paper = Paper::new(&dots);
+        assert_eq!(paper.len(), 18);
+        for &fold in &instructions {
+            paper.fold(fold).unwrap();
+        }
+        assert_eq!(
+            paper.sizes().collect::<Vec<_>>(),
+            [(11, 15), (11, 7), (5, 7)]
+        );
+        assert_eq!(paper.len(), 16);
+        let mut origins = paper.origins((2, 4)).to_vec();
+        origins.sort_unstable();
+        assert_eq!(origins, [(8, 4), (8, 10)]);
+        assert_eq!(paper.origins((0, 0)), [(0, 14)]);
+        assert_eq!(paper.origins((2, 2)), []);
+        assert_eq!(paper.unfold(), Some(FoldInstruction::X(5)));
+        assert_eq!((paper.len(), paper.size()), (17, (11, 7)));
+        assert_eq!(paper.unfold(), Some(FoldInstruction::Y(7)));
+        let mut original = dots.clone();
+        original.sort_unstable();
+        assert_eq!(paper.dots(), original);
+        assert_eq!(paper.unfold(), None);
+    }
+    #[test]
+    fn invalid_folds() {
+        let mut paper = Paper::new(&[(0, 0), (2, 1), (4, 0)]);
+        assert_eq!(
+            paper.fold(FoldInstruction::X(2)),
+            Err(FoldError::DotOnLine((2, 1)))
+        );
+        assert_eq!(
+            paper.fold(FoldInstruction::Y(2)),
+            Err(FoldError::OutsideSheet(FoldInstruction::Y(2)))
+        );
+        assert_eq!(paper.size(), (5, 2));
+        // The longer right side folds over and the sheet shifts right by two.
+        paper.fold(FoldInstruction::X(1)).unwrap();
+        assert_eq!(paper.size(), (3, 2));
+        assert_eq!(paper.dots(), [(0, 0), (2, 0), (2, 1)]);
+        assert_eq!(paper.origins((2, 0)), [(0, 0)]);
+    }
+    #[test]
+    fn blank_edges() {
+        let input = "0,0\n2,0\n\nfold along y=1\n";
+        assert_eq!(part_1(input), 2);
+        let (dots, folds) = parse(input);
+        let mut paper = Paper::for_folds(&dots, &folds);
+        assert_eq!(paper.size(), (3, 3));
+        paper.fold(folds[0]).unwrap();
+        assert_eq!(paper.size(), (3, 1));
+        assert_eq!(render_dots(&paper), "#.#\n");
+        let paper = Paper::with_size(&dots, (4, 2));
+        assert_eq!(render_dots(&paper), "#.#.\n....\n");
+        assert_eq!(Paper::new(&dots).size(), (3, 1));
     }
 }
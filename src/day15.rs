@@ -1,6 +1,8 @@
 use std::cmp::Reverse;
 use std::collections::{BinaryHeap, HashMap};
 
+use crate::image::{Image, WHITE};
+
 type RiskMap = Vec<Vec<u32>>;
 type Pos = (usize, usize);
 
@@ -14,13 +16,23 @@ fn get_neighbors((x, y): Pos, max_x: usize, max_y: usize) -> impl Iterator<Item
 }
 
 fn min_risk_path(risk_map: &RiskMap) -> u32 {
+    min_risk_route(risk_map).0
+}
+
+fn min_risk_route(risk_map: &RiskMap) -> (u32, Vec<Pos>) {
     let end = (risk_map[0].len() - 1, risk_map.len() - 1);
     let mut frontier = BinaryHeap::<Reverse<(u32, Pos)>>::new(); // Need Reverse to get min-heap
     frontier.push(Reverse((0, (0, 0))));
-    let mut min_path_risk_map = HashMap::new();
+    let mut min_path_risk_map = HashMap::from([((0, 0), 0)]);
+    let mut came_from = HashMap::new();
     while let Some(Reverse((curr_risk, curr_pos))) = frontier.pop() {
         if curr_pos == end {
-            return curr_risk;
+            let mut route = vec![end];
+            while let Some(&prev) = came_from.get(route.last().unwrap()) {
+                route.push(prev);
+            }
+            route.reverse();
+            return (curr_risk, route);
         }
         for neighbor_pos in get_neighbors(curr_pos, end.0, end.1) {
             let (nx, ny) = neighbor_pos;
@@ -28,6 +40,7 @@ fn min_risk_path(risk_map: &RiskMap) -> u32 {
             let min_path_risk = min_path_risk_map.entry(neighbor_pos).or_insert(u32::MAX);
             if new_risk < *min_path_risk {
                 *min_path_risk = new_risk;
+                came_from.insert(neighbor_pos, curr_pos);
                 frontier.push(Reverse((new_risk, neighbor_pos)));
             }
         }
@@ -70,12 +83,32 @@ fn part_2(input: &str) -> u32 {
     min_risk_path(&full_map)
 }
 
+pub(crate) fn render(input: &str) -> Image {
+    let risk_map = input
+        .lines()
+        .map(|l| l.chars().map(|c| c.to_digit(10).unwrap()).collect())
+        .collect();
+    render_path(&risk_map)
+}
+
+// Risk as shades of grey with the safest route drawn on top.
+fn render_path(risk_map: &RiskMap) -> Image {
+    let mut image = Image::from_grid(risk_map, |&risk| [(risk * 25) as u8; 3]);
+    for (x, y) in min_risk_route(risk_map).1 {
+        image.set(x, y, [255, 0, 0]);
+    }
+    image.set(0, 0, WHITE);
+    image
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
 
+    use super::min_risk_route;
     use super::part_1;
     use super::part_2;
+    use super::render_path;
     #[test]
     fn example15_part1() {
         let input = fs::read_to_string("input/example15").unwrap();
@@ -100,4 +133,21 @@ mod tests {
         let ans = part_2(&input);
         assert_eq!(ans, 2868);
     }
+    #[test]
+    fn example15_route() {
+        let input = fs::read_to_string("input/example15").unwrap();
+        let risk_map = input
+            .lines()
+            .map(|l| l.chars().map(|c| c.to_digit(10).unwrap()).collect())
+            .collect();
+        let (risk, route) = min_risk_route(&risk_map);
+        assert_eq!(risk, 40);
+        assert_eq!(route.first(), Some(&(0, 0)));
+        assert_eq!(route.last(), Some(&(9, 9)));
+        let route_risk: u32 = route[1..].iter().map(|&(x, y)| risk_map[y][x]).sum();
+        assert_eq!(route_risk, 40);
+        let image = render_path(&risk_map);
+        assert_eq!(image.get(9, 9), [255, 0, 0]);
+        assert_eq!(image.get(9, 0), [50; 3]);
+    }
 }
@@ -1,9 +1,11 @@
 use bitvec::{field::BitField, prelude::*};
 use std::collections::HashMap;
 
+use crate::image::{Image, BLACK, WHITE};
+
 fn parse(s: &str) -> (Vec<bool>, HashMap<(i64, i64), bool>) {
-    let empty_line = "\r\n\r\n";
-    let (enhancement_table, image) = s.split_once(empty_line).unwrap();
+    let s = s.replace("\r\n", "\n");
+    let (enhancement_table, image) = s.split_once("\n\n").unwrap();
     let enhancement_table: Vec<_> = enhancement_table.chars().map(|c| c == '#').collect();
     let image: HashMap<(i64, i64), bool> = image
         .lines()
@@ -42,6 +44,10 @@ fn bounds(image: &HashMap<(i64, i64), bool>) -> ((i64, i64), (i64, i64)) {
 }
 
 fn part_1(s: &str, steps: usize) -> usize {
+    enhance(s, steps).values().filter(|v| **v).count()
+}
+
+fn enhance(s: &str, steps: usize) -> HashMap<(i64, i64), bool> {
     let (enhancement, mut image) = parse(s);
     let ((mut min_x, mut min_y), (mut max_x, mut max_y)) = bounds(&image);
     for step in 0..steps {
@@ -58,13 +64,28 @@ fn part_1(s: &str, steps: usize) -> usize {
         }
         image = next_image;
     }
-    image.values().filter(|v| **v).count()
+    image
 }
+
+// The image after the two enhancement steps of part 1.
+pub(crate) fn render(input: &str) -> Image {
+    render_image(&enhance(input, 2))
+}
+
+fn render_image(image: &HashMap<(i64, i64), bool>) -> Image {
+    let ((min_x, min_y), (max_x, max_y)) = bounds(image);
+    let grid: Vec<Vec<bool>> = (min_y..=max_y)
+        .map(|y| (min_x..=max_x).map(|x| image[&(x, y)]).collect())
+        .collect();
+    Image::from_grid(&grid, |&lit| if lit { WHITE } else { BLACK })
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
 
-    use super::part_1;
+    use super::{enhance, part_1, render_image};
+    use crate::image::{BLACK, WHITE};
     #[test]
     fn example() {
         let input = fs::read_to_string("input/example20").unwrap();
@@ -86,4 +107,18 @@ mod tests {
         let ans = part_1(&input, 50);
         assert_eq!(ans, 16826);
     }
+
+    #[test]
+    fn day20_render() {
+        let input = fs::read_to_string("input/day20").unwrap();
+        let image = enhance(&input, 2);
+        let rendered = render_image(&image);
+        let (width, height) = (rendered.width(), rendered.height());
+        let lit = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| rendered.get(x, y) == WHITE)
+            .count();
+        assert_eq!(lit, 5361);
+        assert_eq!(rendered.get(0, 0), BLACK);
+    }
 }
@@ -1,123 +1,94 @@
-use std::{
-    collections::HashMap,
-    ops::{RangeBounds, RangeInclusive},
-};
+use std::{fs, io, ops::RangeInclusive, path::Path};
 
-use itertools::Itertools;
+use bitvec::prelude::*;
+use itertools::{iproduct, Itertools};
 
-fn reboot_reactor_part1(s: &str) -> HashMap<(i32, i32, i32), bool> {
-    let range = |r: &str| {
-        let (start, end) = r.split_once("..").unwrap();
-        (start.parse::<i32>().unwrap(), end.parse::<i32>().unwrap())
-    };
-    let mut res = HashMap::new();
-    for l in s.lines() {
-        println!("new range");
-        let (on_off, ranges) = l.split_once(' ').unwrap();
-        let ranges = ranges
-            .split(',')
-            .map(|r| r.split_once('=').unwrap().1)
-            .map(|r| range(r))
-            .filter(|&(s, e)| (-50 < s || -50 < e) && (s <= 50 || e <= 50))
-            .map(|(start, end)| start..=end)
-            .collect_vec();
-        if ranges.len() < 3 {
-            continue;
-        }
-        let iter = ranges
-            .into_iter()
-            .multi_cartesian_product()
-            .map(|v| ((v[0], v[1], v[2]), on_off == "on"))
-            .filter(|(pos, _)| part_1_range(*pos));
-
-        res.extend(iter);
-    }
-    res
-}
+use crate::image::Image;
 
-fn part_1(s: &str) -> usize {
-    let reactor = reboot_reactor_part1(s);
-    reactor.iter().filter(|(_, on)| **on).count()
+fn part_1(input: &str) -> u64 {
+    let region = Cuboid {
+        on: true,
+        ranges: [-50..=50, -50..=50, -50..=50],
+    };
+    region_volume(input, &region)
 }
 
-fn part_1_range((x, y, z): (i32, i32, i32)) -> bool {
-    -50 <= x && x <= 50 && -50 <= y && y <= 50 && -50 <= z && z <= 50
+fn region_volume<const N: usize>(input: &str, region: &Cuboid<N>) -> u64 {
+    let cuboids = parse_steps(input)
+        .into_iter()
+        .filter_map(|c| c.clip(region))
+        .collect_vec();
+    Fragmenting.volume(&cuboids)
 }
 
 ////////// Part 2
 
-#[derive(Clone)]
-struct Cuboid {
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cuboid<const N: usize> {
     on: bool,
-    x_range: RangeInclusive<i64>,
-    y_range: RangeInclusive<i64>,
-    z_range: RangeInclusive<i64>,
+    ranges: [RangeInclusive<i64>; N],
 }
 
-impl Cuboid {
+impl<const N: usize> Cuboid<N> {
     fn parse(s: &str) -> Self {
         let (on_off, ranges) = s.split_once(' ').unwrap();
-        let ranges = ranges
-            .split(',')
-            .map(|r| r.split_once('=').unwrap().1)
-            .collect_vec();
         let read_range = |r: &str| {
             let (start, end) = r.split_once("..").unwrap();
             start.parse::<i64>().unwrap()..=end.parse::<i64>().unwrap()
         };
-        let x_range = read_range(ranges[0]);
-        let y_range = read_range(ranges[1]);
-        let z_range = read_range(ranges[2]);
+        let ranges = ranges
+            .split(',')
+            .map(|r| r.split_once('=').unwrap().1)
+            .map(read_range)
+            .collect_vec();
         let on = "on" == on_off;
         Self {
             on,
-            x_range,
-            y_range,
-            z_range,
+            ranges: ranges.try_into().unwrap(),
         }
     }
     fn volume(&self) -> u64 {
-        self.x_range.size_hint().0 as u64
-            * self.y_range.size_hint().0 as u64
-            * self.z_range.size_hint().0 as u64
-    }
-
-    fn subtract(&self, subber: &Cuboid) -> Option<Vec<Cuboid>> {
-        let overlap_x = overlap_range(self.x_range.clone(), subber.x_range.clone())?;
-        let overlap_y = overlap_range(self.y_range.clone(), subber.y_range.clone())?;
-        let overlap_z = overlap_range(self.z_range.clone(), subber.z_range.clone())?;
-        let x_ranges = [
-            *self.x_range.start()..=*overlap_x.start() - 1,
-            overlap_x.clone(),
-            *overlap_x.end() + 1..=*self.x_range.end(),
-        ];
-        let y_ranges = [
-            *self.y_range.start()..=*overlap_y.start() - 1,
-            overlap_y.clone(),
-            *overlap_y.end() + 1..=*self.y_range.end(),
-        ];
-        let z_ranges = [
-            *self.z_range.start()..=*overlap_z.start() - 1,
-            overlap_z.clone(),
-            *overlap_z.end() + 1..=*self.z_range.end(),
-        ];
-        let mut remainder = vec![];
-        [x_ranges, y_ranges, z_ranges]
+        self.ranges.iter().map(|r| r.size_hint().0 as u64).product()
+    }
+
+    fn clip(&self, region: &Cuboid<N>) -> Option<Cuboid<N>> {
+        let mut ranges = self.ranges.clone();
+        for (range, other) in ranges.iter_mut().zip(&region.ranges) {
+            *range = overlap_range(range.clone(), other.clone())?;
+        }
+        Some(Cuboid {
+            on: self.on,
+            ranges,
+        })
+    }
+
+    fn subtract(&self, subber: &Cuboid<N>) -> Option<Vec<Cuboid<N>>> {
+        let overlap = self.clip(subber)?;
+        let splits = self
+            .ranges
             .iter()
-            .multi_cartesian_product()
-            .for_each(|ranges| {
-                let is_overlap = ranges[0].start() == overlap_x.start()
-                    && ranges[1].start() == overlap_y.start()
-                    && ranges[2].start() == overlap_z.start();
-                if ranges.iter().all(|r| !r.is_empty()) && !is_overlap {
-                    remainder.push(Cuboid {
-                        on: true,
-                        x_range: ranges[0].clone(),
-                        y_range: ranges[1].clone(),
-                        z_range: ranges[2].clone(),
-                    });
-                }
-            });
+            .zip(&overlap.ranges)
+            .map(|(range, overlap)| {
+                [
+                    *range.start()..=*overlap.start() - 1,
+                    overlap.clone(),
+                    *overlap.end() + 1..=*range.end(),
+                ]
+            })
+            .collect_vec();
+        let mut remainder = vec![];
+        splits.iter().multi_cartesian_product().for_each(|ranges| {
+            let is_overlap = ranges
+                .iter()
+                .zip(&overlap.ranges)
+                .all(|(r, o)| r.start() == o.start());
+            if ranges.iter().all(|r| !r.is_empty()) && !is_overlap {
+                remainder.push(Cuboid {
+                    on: true,
+                    ranges: std::array::from_fn(|axis| ranges[axis].clone()),
+                });
+            }
+        });
         Some(remainder)
     }
 }
@@ -137,7 +108,10 @@ fn overlap_range(
     }
 }
 
-fn cuboid_subtraction(cuboids: Vec<Cuboid>, subber: &Cuboid) -> Vec<Cuboid> {
+fn cuboid_subtraction<const N: usize>(
+    cuboids: Vec<Cuboid<N>>,
+    subber: &Cuboid<N>,
+) -> Vec<Cuboid<N>> {
     let mut rem = vec![];
     for c in cuboids {
         if let Some(r) = c.subtract(subber) {
@@ -150,30 +124,330 @@ fn cuboid_subtraction(cuboids: Vec<Cuboid>, subber: &Cuboid) -> Vec<Cuboid> {
 }
 
 fn part_2(input: &str) -> u64 {
-    let cuboids = input.lines().map(Cuboid::parse).collect_vec();
-    let mut cuboids_on: Vec<Cuboid> = vec![];
+    part_2_with(input, &Fragmenting)
+}
+
+fn part_2_with(input: &str, strategy: &impl VolumeStrategy) -> u64 {
+    let cuboids: Vec<Cuboid<3>> = parse_steps(input);
+    strategy.volume(&cuboids)
+}
+
+fn parse_steps<const N: usize>(input: &str) -> Vec<Cuboid<N>> {
+    input.lines().map(Cuboid::parse).collect()
+}
+
+trait VolumeStrategy {
+    fn volume<const N: usize>(&self, cuboids: &[Cuboid<N>]) -> u64;
+}
+
+// Splits every lit cuboid into up to 26 disjoint pieces around each overlap.
+struct Fragmenting;
+
+impl VolumeStrategy for Fragmenting {
+    fn volume<const N: usize>(&self, cuboids: &[Cuboid<N>]) -> u64 {
+        disjoint_cuboids_on(cuboids)
+            .iter()
+            .map(Cuboid::volume)
+            .sum()
+    }
+}
+
+fn disjoint_cuboids_on<const N: usize>(cuboids: &[Cuboid<N>]) -> Vec<Cuboid<N>> {
+    let mut cuboids_on: Vec<Cuboid<N>> = vec![];
     for c in cuboids {
         if c.on {
-            cuboids_on.push(c);
+            cuboids_on.push(c.clone());
         } else {
-            cuboids_on = cuboid_subtraction(cuboids_on, &c);
+            cuboids_on = cuboid_subtraction(cuboids_on, c);
         }
     }
 
-    let mut volume = 0;
+    let mut disjoint = vec![];
     while let Some(next) = cuboids_on.pop() {
-        volume += next.volume();
         cuboids_on = cuboid_subtraction(cuboids_on, &next);
+        disjoint.push(next);
+    }
+    disjoint
+}
+
+// Keeps a list of signed cuboids; every overlap with an earlier entry is
+// added back with the opposite sign so nothing is counted twice.
+struct InclusionExclusion;
+
+impl VolumeStrategy for InclusionExclusion {
+    fn volume<const N: usize>(&self, cuboids: &[Cuboid<N>]) -> u64 {
+        let mut signed: Vec<(Cuboid<N>, i64)> = vec![];
+        for c in cuboids {
+            let overlaps = signed
+                .iter()
+                .filter_map(|(s, sign)| Some((s.clip(c)?, -sign)))
+                .collect_vec();
+            signed.extend(overlaps);
+            if c.on {
+                signed.push((c.clone(), 1));
+            }
+        }
+        let volume: i64 = signed
+            .iter()
+            .map(|(c, sign)| c.volume() as i64 * sign)
+            .sum();
+        volume as u64
+    }
+}
+
+// Maps every cuboid edge onto a compressed grid index and paints the cells
+// of an N-dimensional bit grid in input order; each cell stands for a whole block.
+struct CoordinateCompression;
+
+impl VolumeStrategy for CoordinateCompression {
+    fn volume<const N: usize>(&self, cuboids: &[Cuboid<N>]) -> u64 {
+        if cuboids.is_empty() {
+            return 0;
+        }
+        let edges = (0..N)
+            .map(|axis| {
+                cuboids
+                    .iter()
+                    .flat_map(|c| [*c.ranges[axis].start(), *c.ranges[axis].end() + 1])
+                    .sorted()
+                    .dedup()
+                    .collect_vec()
+            })
+            .collect_vec();
+        let index = |edges: &[i64], range: &RangeInclusive<i64>| {
+            let start = edges.binary_search(range.start()).unwrap();
+            let end = edges.binary_search(&(range.end() + 1)).unwrap();
+            start..end
+        };
+        // The last axis forms the rows, padded to whole words so they can be
+        // read back directly; the other axes are flattened into a row index.
+        let (last, outer) = edges.split_last().unwrap();
+        let row_words = (last.len() - 1).div_ceil(64);
+        let row_count: usize = outer.iter().map(|e| e.len() - 1).product();
+        let mut grid = bitvec![Lsb0, u64; 0; row_count * row_words * 64];
+        for c in cuboids {
+            let cells = index(last, &c.ranges[N - 1]);
+            let rows = outer
+                .iter()
+                .zip(&c.ranges)
+                .fold(vec![0], |rows, (edges, range)| {
+                    let size = edges.len() - 1;
+                    rows.iter()
+                        .flat_map(|row| index(edges, range).map(move |i| row * size + i))
+                        .collect()
+                });
+            for row in rows {
+                let row = row * row_words * 64;
+                grid[row + cells.start..row + cells.end].set_all(c.on);
+            }
+        }
+        let mut volume = 0;
+        for (row, words) in grid.as_raw_slice().chunks(row_words).enumerate() {
+            let mut area = 1;
+            let mut rest = row;
+            for edges in outer.iter().rev() {
+                let i = rest % (edges.len() - 1);
+                area *= (edges[i + 1] - edges[i]) as u64;
+                rest /= edges.len() - 1;
+            }
+            for (w, &word) in words.iter().enumerate() {
+                let mut word = word;
+                while word != 0 {
+                    let i = w * 64 + word.trailing_zeros() as usize;
+                    volume += area * (last[i + 1] - last[i]) as u64;
+                    word &= word - 1;
+                }
+            }
+        }
+        volume
     }
-    volume
+}
+
+////////// Export
+
+fn reboot(input: &str) -> Vec<Cuboid<3>> {
+    disjoint_cuboids_on(&parse_steps(input))
+}
+
+// The format follows the extension: .csv, .json or .obj.
+fn export_cuboids(cuboids: &[Cuboid<3>], path: &Path) -> io::Result<()> {
+    let contents = match path.extension().and_then(|e| e.to_str()) {
+        Some("csv") => to_csv(cuboids),
+        Some("json") => to_json(cuboids),
+        Some("obj") => to_obj(cuboids),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("can't export cuboids to {}", path.display()),
+            ))
+        }
+    };
+    fs::write(path, contents)
+}
+
+fn to_csv<const N: usize>(cuboids: &[Cuboid<N>]) -> String {
+    let header = (0..N)
+        .map(|axis| format!("start{axis},end{axis}"))
+        .join(",");
+    let rows = cuboids.iter().map(|c| {
+        c.ranges
+            .iter()
+            .map(|r| format!("{},{}", r.start(), r.end()))
+            .join(",")
+    });
+    std::iter::once(header).chain(rows).join("\n") + "\n"
+}
+
+// Reads back what `to_csv` wrote; every cuboid comes back switched on.
+fn from_csv<const N: usize>(csv: &str) -> Vec<Cuboid<N>> {
+    csv.lines()
+        .skip(1)
+        .map(|row| {
+            let bounds: Vec<i64> = row.split(',').map(|b| b.parse().unwrap()).collect();
+            let ranges = bounds.chunks(2).map(|b| b[0]..=b[1]).collect_vec();
+            Cuboid {
+                on: true,
+                ranges: ranges.try_into().unwrap(),
+            }
+        })
+        .collect()
+}
+
+// An array of objects with the bounds along each axis as [start, end] pairs.
+fn to_json<const N: usize>(cuboids: &[Cuboid<N>]) -> String {
+    let mut objects = cuboids.iter().map(|c| {
+        let ranges = c
+            .ranges
+            .iter()
+            .map(|r| format!("[{}, {}]", r.start(), r.end()))
+            .join(", ");
+        format!("  {{\"on\": {}, \"ranges\": [{ranges}]}}", c.on)
+    });
+    format!("[\n{}\n]\n", objects.join(",\n"))
+}
+
+// Each cuboid becomes 8 corner vertices and 6 quads; the far corner sits at
+// end + 1 so that unit cells have unit size.
+fn to_obj(cuboids: &[Cuboid<3>]) -> String {
+    const FACES: [[usize; 4]; 6] = [
+        [0, 2, 3, 1],
+        [4, 5, 7, 6],
+        [0, 1, 5, 4],
+        [2, 6, 7, 3],
+        [0, 4, 6, 2],
+        [1, 3, 7, 5],
+    ];
+    let mut obj = String::new();
+    for (n, c) in cuboids.iter().enumerate() {
+        let [x, y, z] = &c.ranges;
+        for (x, y, z) in iproduct!(
+            [*x.start(), x.end() + 1],
+            [*y.start(), y.end() + 1],
+            [*z.start(), z.end() + 1]
+        ) {
+            obj += &format!("v {x} {y} {z}\n");
+        }
+        for face in FACES {
+            let corners = face.iter().map(|i| n * 8 + i + 1).join(" ");
+            obj += &format!("f {corners}\n");
+        }
+    }
+    obj
+}
+
+fn slice(cuboids: &[Cuboid<3>], z: i64) -> Vec<Cuboid<2>> {
+    cuboids
+        .iter()
+        .filter(|c| c.ranges[2].contains(&z))
+        .map(|c| Cuboid {
+            on: c.on,
+            ranges: [c.ranges[0].clone(), c.ranges[1].clone()],
+        })
+        .collect()
+}
+
+// Lit area inside every scale x scale pixel of the window, row by row along y.
+// A non-positive scale or an empty window has no pixels at all.
+fn coverage(rects: &[Cuboid<2>], window: &Cuboid<2>, scale: i64) -> Vec<Vec<u64>> {
+    let [x_range, y_range] = &window.ranges;
+    if scale <= 0 || x_range.is_empty() || y_range.is_empty() {
+        return vec![];
+    }
+    let pixels = |r: &RangeInclusive<i64>| r.clone().step_by(scale as usize);
+    pixels(y_range)
+        .map(|y| {
+            pixels(x_range)
+                .map(|x| {
+                    let pixel = Cuboid {
+                        on: true,
+                        ranges: [x..=x + scale - 1, y..=y + scale - 1],
+                    };
+                    rects
+                        .iter()
+                        .filter_map(|r| r.clip(&pixel))
+                        .map(|r| r.volume())
+                        .sum()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn render_ascii(rects: &[Cuboid<2>], window: &Cuboid<2>, scale: i64) -> String {
+    let full = (scale * scale) as u64;
+    coverage(rects, window, scale)
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|&area| match area {
+                    0 => '.',
+                    a if a == full => '#',
+                    _ => '+',
+                })
+                .collect::<String>()
+        })
+        .join("\n")
+}
+
+fn render_slice(rects: &[Cuboid<2>], window: &Cuboid<2>, scale: i64) -> Image {
+    let full = (scale * scale) as u64;
+    Image::from_grid(&coverage(rects, window, scale), |area| {
+        [(area * 255 / full) as u8; 3]
+    })
 }
 
 #[cfg(test)]
 mod tests {
-    use std::fs;
+    use std::{env, fs, path::Path};
 
+    use super::export_cuboids;
+    use super::from_csv;
+    use super::parse_steps;
     use super::part_1;
     use super::part_2;
+    use super::part_2_with;
+    use super::reboot;
+    use super::region_volume;
+    use super::render_ascii;
+    use super::render_slice;
+    use super::slice;
+    use super::to_csv;
+    use super::to_obj;
+    use super::CoordinateCompression;
+    use super::Cuboid;
+    use super::Fragmenting;
+    use super::InclusionExclusion;
+    use super::VolumeStrategy;
+    const RECTANGLES: &str = "on x=0..9,y=0..9
+off x=5..14,y=5..14
+on x=8..8,y=8..8";
+    const HYPERCUBES: &str = "on x=0..1,y=0..1,z=0..1,w=0..1
+on x=1..2,y=1..2,z=1..2,w=1..2
+off x=2..2,y=2..2,z=2..2,w=2..2";
+    const SMALL: &str = "on x=10..12,y=10..12,z=10..12
+on x=11..13,y=11..13,z=11..13
+off x=9..11,y=9..11,z=9..11
+on x=10..10,y=10..10,z=10..10";
     #[test]
     fn example22_part1() {
         let input = fs::read_to_string("input/example22").unwrap();
@@ -186,6 +460,14 @@ mod tests {
         let ans = part_1(&input);
         assert_eq!(ans, 589411);
     }
+    #[test]
+    fn day22_region() {
+        let input = fs::read_to_string("input/day22").unwrap();
+        let region: Cuboid<3> =
+            Cuboid::parse("on x=-100000..100000,y=-100000..100000,z=-100000..100000");
+        let ans = region_volume(&input, &region);
+        assert_eq!(ans, 1130514303649907);
+    }
 
     #[test]
     fn example22_part2() {
@@ -199,4 +481,85 @@ mod tests {
         let ans = part_2(&input);
         assert_eq!(ans, 1130514303649907);
     }
+    #[test]
+    fn strategies_agree() {
+        for file in ["input/example22_part2", "input/day22"] {
+            let input = fs::read_to_string(file).unwrap();
+            let fragmenting = part_2_with(&input, &Fragmenting);
+            assert_eq!(part_2_with(&input, &InclusionExclusion), fragmenting);
+            assert_eq!(part_2_with(&input, &CoordinateCompression), fragmenting);
+        }
+    }
+    #[test]
+    fn rectangles() {
+        let steps: Vec<Cuboid<2>> = parse_steps(RECTANGLES);
+        assert_eq!(Fragmenting.volume(&steps), 76);
+        assert_eq!(InclusionExclusion.volume(&steps), 76);
+        assert_eq!(CoordinateCompression.volume(&steps), 76);
+    }
+    #[test]
+    fn hypercubes() {
+        let steps: Vec<Cuboid<4>> = parse_steps(HYPERCUBES);
+        assert_eq!(Fragmenting.volume(&steps), 30);
+        assert_eq!(InclusionExclusion.volume(&steps), 30);
+        assert_eq!(CoordinateCompression.volume(&steps), 30);
+    }
+    #[test]
+    fn export_small() {
+        let cuboids = reboot(SMALL);
+        assert_eq!(cuboids.iter().map(Cuboid::volume).sum::<u64>(), 39);
+        let csv = to_csv(&cuboids);
+        assert!(csv.starts_with("start0,end0,start1,end1,start2,end2\n"));
+        assert_eq!(csv.lines().count(), cuboids.len() + 1);
+        let obj = to_obj(&cuboids);
+        assert_eq!(
+            obj.lines().filter(|l| l.starts_with("v ")).count(),
+            8 * cuboids.len()
+        );
+        assert_eq!(
+            obj.lines().filter(|l| l.starts_with("f ")).count(),
+            6 * cuboids.len()
+        );
+    }
+    #[test]
+    fn slice_small() {
+        let cuboids = reboot(SMALL);
+        let window = Cuboid::parse("on x=9..13,y=9..13");
+        let ascii = render_ascii(&slice(&cuboids, 11), &window, 1);
+        assert_eq!(ascii, ".....\n...#.\n...##\n.####\n..###");
+        let window = Cuboid::parse("on x=10..13,y=10..13");
+        let ascii = render_ascii(&slice(&cuboids, 11), &window, 2);
+        assert_eq!(ascii, ".+\n+#");
+    }
+    #[test]
+    fn export_round_trip() {
+        let cuboids = reboot(SMALL);
+        let dir = env::temp_dir();
+        let csv = dir.join("day22_export_round_trip.csv");
+        export_cuboids(&cuboids, &csv).unwrap();
+        assert_eq!(from_csv::<3>(&fs::read_to_string(&csv).unwrap()), cuboids);
+        fs::remove_file(&csv).unwrap();
+        let json = dir.join("day22_export_round_trip.json");
+        export_cuboids(&cuboids, &json).unwrap();
+        let written = fs::read_to_string(&json).unwrap();
+        fs::remove_file(&json).unwrap();
+        assert!(written.starts_with("[\n  {\"on\": true, \"ranges\": [["));
+        assert_eq!(written.matches("\"on\"").count(), cuboids.len());
+        let err = export_cuboids(&cuboids, Path::new("cuboids.txt")).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+    #[test]
+    fn render_slice_small() {
+        let cuboids = reboot(SMALL);
+        let window = Cuboid::parse("on x=10..13,y=10..13");
+        let image = render_slice(&slice(&cuboids, 11), &window, 2);
+        assert_eq!((image.width(), image.height()), (2, 2));
+        assert_eq!(image.get(0, 0), [0; 3]);
+        assert_eq!(image.get(1, 1), [255; 3]);
+        assert_eq!(image.get(1, 0), [191; 3]);
+        let image = render_slice(&slice(&cuboids, 11), &window, 0);
+        assert_eq!((image.width(), image.height()), (0, 0));
+        let empty = Cuboid::parse("on x=13..10,y=10..13");
+        assert_eq!(render_ascii(&slice(&cuboids, 11), &empty, 1), "");
+    }
 }
@@ -1,3 +1,5 @@
+use crate::image::{Image, BLACK};
+
 enum Tile {
     Empty,
     Right,
@@ -79,17 +81,37 @@ fn parse_tiles(s: &str) -> Tiles {
 
 fn part_1(input: &str) -> u32 {
     let mut tiles = parse_tiles(input);
+    run_until_settled(&mut tiles)
+}
+
+fn run_until_settled(tiles: &mut Tiles) -> u32 {
     for i in 1.. {
-        if !step(&mut tiles) {
+        if !step(tiles) {
             return i;
         }
     }
     unreachable!()
 }
 
+// The herds once they have stopped moving.
+pub(crate) fn render(input: &str) -> Image {
+    let mut tiles = parse_tiles(input);
+    run_until_settled(&mut tiles);
+    render_herds(&tiles)
+}
+
+fn render_herds(tiles: &Tiles) -> Image {
+    Image::from_grid(tiles, |tile| match tile {
+        Tile::Empty => BLACK,
+        Tile::Right => [255, 140, 0],
+        Tile::Down => [30, 144, 255],
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use super::part_1;
+    use super::{parse_tiles, part_1, render_herds, run_until_settled};
+    use crate::image::BLACK;
     use std::fs;
     #[test]
     fn example25_part1() {
@@ -101,4 +123,14 @@ mod tests {
         let input = fs::read_to_string("input/day25").unwrap();
         assert_eq!(part_1(&input), 598);
     }
+    #[test]
+    fn example25_render() {
+        let input = fs::read_to_string("input/example25").unwrap();
+        let mut tiles = parse_tiles(&input);
+        assert_eq!(run_until_settled(&mut tiles), 58);
+        let image = render_herds(&tiles);
+        assert_eq!(image.get(0, 0), BLACK);
+        assert_eq!(image.get(5, 0), [255, 140, 0]);
+        assert_eq!(image.get(7, 0), [30, 144, 255]);
+    }
 }
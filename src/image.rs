@@ -0,0 +1,162 @@
+use std::{fs, io, path::Path};
+
+pub(crate) type Rgb = [u8; 3];
+
+pub(crate) const BLACK: Rgb = [0, 0, 0];
+pub(crate) const WHITE: Rgb = [255, 255, 255];
+
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl Image {
+    pub(crate) fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![BLACK; width * height],
+        }
+    }
+
+    pub(crate) fn from_grid<T>(grid: &[Vec<T>], color: impl Fn(&T) -> Rgb) -> Self {
+        let mut image = Image::new(grid.first().map_or(0, Vec::len), grid.len());
+        for (y, row) in grid.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                image.set(x, y, color(cell));
+            }
+        }
+        image
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub(crate) fn get(&self, x: usize, y: usize) -> Rgb {
+        self.pixels[y * self.width + x]
+    }
+
+    pub(crate) fn set(&mut self, x: usize, y: usize, color: Rgb) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    // Every pixel becomes a factor x factor block, so small grids stay visible.
+    pub fn scaled(&self, factor: usize) -> Image {
+        let mut image = Image::new(self.width * factor, self.height * factor);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                image.set(x, y, self.get(x / factor, y / factor));
+            }
+        }
+        image
+    }
+
+    pub(crate) fn to_ppm(&self) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        ppm.extend(self.pixels.iter().flatten());
+        ppm
+    }
+
+    pub(crate) fn to_pgm(&self) -> Vec<u8> {
+        let mut pgm = format!("P5\n{} {}\n255\n", self.width, self.height).into_bytes();
+        pgm.extend(
+            self.pixels.iter().map(|&[r, g, b]| {
+                ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+            }),
+        );
+        pgm
+    }
+
+    // Greyscale for a .pgm path, colour otherwise.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("pgm") => fs::write(path, self.to_pgm()),
+            Some("ppm") => fs::write(path, self.to_ppm()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("can't write an image to {}", path.display()),
+            )),
+        }
+    }
+}
+
+pub(crate) struct Palette(Vec<Rgb>);
+
+impl Palette {
+    pub(crate) fn new(colors: &[Rgb]) -> Self {
+        Palette(colors.to_vec())
+    }
+
+    // Hues spread around the colour wheel by the golden angle, so neighbouring
+    // indices get clearly different colours.
+    pub(crate) fn distinct(count: usize) -> Self {
+        Palette(
+            (0..count)
+                .map(|i| hue((i as f64 * 137.508) % 360.0))
+                .collect(),
+        )
+    }
+
+    // Indices wrap around when there are more of them than colours.
+    pub(crate) fn color(&self, index: usize) -> Rgb {
+        self.0[index % self.0.len()]
+    }
+}
+
+fn hue(degrees: f64) -> Rgb {
+    let x = 1.0 - ((degrees / 60.0) % 2.0 - 1.0).abs();
+    let (r, g, b) = match degrees as u32 / 60 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    [(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8]
+}
+
+// Black through red and yellow to white as value goes from 0 to max.
+pub(crate) fn heat(value: u32, max: u32) -> Rgb {
+    let t = value.min(max) as f64 / max.max(1) as f64 * 3.0;
+    let channel = |offset: f64| ((t - offset).clamp(0.0, 1.0) * 255.0) as u8;
+    [channel(0.0), channel(1.0), channel(2.0)]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{heat, Image, Palette, BLACK, WHITE};
+
+    #[test]
+    fn ppm_and_pgm() {
+        let mut image =
+            Image::from_grid(&[vec![true, false]], |&on| if on { WHITE } else { BLACK });
+        image.set(1, 0, [255, 0, 0]);
+        assert_eq!(image.to_ppm(), b"P6\n2 1\n255\n\xff\xff\xff\xff\x00\x00");
+        assert_eq!(image.to_pgm(), b"P5\n2 1\n255\n\xff\x4c");
+        let scaled = image.scaled(2);
+        assert_eq!(scaled.get(3, 1), [255, 0, 0]);
+        assert_eq!(&scaled.to_ppm()[..11], b"P6\n4 2\n255\n");
+        let err = image.write(Path::new("image.png")).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn palettes() {
+        let palette = Palette::distinct(3);
+        assert_eq!(palette.color(0), [255, 0, 0]);
+        assert_ne!(palette.color(1), palette.color(2));
+        assert_eq!(palette.color(3), palette.color(0));
+        assert_eq!(Palette::new(&[WHITE]).color(7), WHITE);
+        assert_eq!(heat(0, 9), BLACK);
+        assert_eq!(heat(9, 9), WHITE);
+    }
+}
@@ -22,3 +22,25 @@ mod day20;
 mod day21;
 mod day22;
 mod day25;
+pub mod image;
+
+use image::Image;
+
+// Renders the interesting state of a day's puzzle for the given input, or
+// None if the day has no renderer.
+pub fn render(day: u32, input: &str) -> Option<Image> {
+    match day {
+        9 => Some(day09::render(input)),
+        11 => Some(day11::render(input)),
+        15 => Some(day15::render(input)),
+        20 => Some(day20::render(input)),
+        25 => Some(day25::render(input)),
+        _ => None,
+    }
+}
+
+// Balances every line of a day 10 navigation file with the fewest edits,
+// returning the repaired file and the number of edits made.
+pub fn repair_navigation(input: &str) -> (String, usize) {
+    day10::repair_lines(input)
+}
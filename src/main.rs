@@ -0,0 +1,63 @@
+use std::{env, fs, path::Path, process};
+
+use advent_of_code_2021::{render, repair_navigation};
+
+const USAGE: &str = "usage: advent_of_code_2021 <day> <input> --render <out.ppm|out.pgm>
+       advent_of_code_2021 10 <input> --repair [<out>]";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.as_slice() {
+        [day, input, flag, out] if flag == "--render" => render_day(day, input, out),
+        [day, input, flag, rest @ ..] if day == "10" && flag == "--repair" && rest.len() <= 1 => {
+            repair_file(input, rest.first())
+        }
+        _ => usage(),
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{USAGE}");
+    process::exit(2);
+}
+
+fn read_input(input: &str) -> String {
+    fs::read_to_string(input).unwrap_or_else(|e| {
+        eprintln!("can't read {input}: {e}");
+        process::exit(1);
+    })
+}
+
+fn render_day(day: &str, input: &str, out: &str) {
+    let day: u32 = day.parse().unwrap_or_else(|_| usage());
+    let image = render(day, &read_input(input)).unwrap_or_else(|| {
+        eprintln!("day {day} has no renderer");
+        process::exit(1);
+    });
+    let size = image.width().max(image.height());
+    if size == 0 {
+        eprintln!("day {day} has nothing to render");
+        process::exit(1);
+    }
+    // Puzzle grids are small; blow them up to a few hundred pixels.
+    let factor = (400 / size).max(1);
+    if let Err(e) = image.scaled(factor).write(Path::new(out)) {
+        eprintln!("can't write {out}: {e}");
+        process::exit(1);
+    }
+}
+
+// Writes the repaired file to `out`, or to stdout without one.
+fn repair_file(input: &str, out: Option<&String>) {
+    let (repaired, edits) = repair_navigation(&read_input(input));
+    match out {
+        Some(out) => {
+            if let Err(e) = fs::write(out, repaired) {
+                eprintln!("can't write {out}: {e}");
+                process::exit(1);
+            }
+        }
+        None => print!("{repaired}"),
+    }
+    eprintln!("{edits} edits");
+}